@@ -1,11 +1,8 @@
-use futures::{
-    channel::mpsc::{channel, Receiver},
-    SinkExt, StreamExt,
-};
-use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher, Config};
+use futures::StreamExt;
+use notify::{stream::EventStream, Config, RecommendedWatcher, RecursiveMode, Watcher};
 use std::path::Path;
 
-/// Async, futures channel based event watching
+/// Async, stream based event watching
 fn main() {
     let path = std::env::args()
         .nth(1)
@@ -19,28 +16,16 @@ fn main() {
     });
 }
 
-fn async_watcher() -> notify::Result<(RecommendedWatcher, Receiver<notify::Result<Event>>)> {
-    let (mut tx, rx) = channel(1);
-
-    // Automatically select the best implementation for your platform.
-    // You can also access each implementation directly e.g. INotifyWatcher.
-    let watcher = RecommendedWatcher::new(move |res| {
-        futures::executor::block_on(async {
-            tx.send(res).await.unwrap();
-        })
-    }, Config::default())?;
-
-    Ok((watcher, rx))
-}
-
 async fn async_watch<P: AsRef<Path>>(path: P) -> notify::Result<()> {
-    let (mut watcher, mut rx) = async_watcher()?;
+    // Automatically select the best implementation for your platform.
+    // You can also use each implementation directly e.g. INotifyWatcher.
+    let (mut watcher, mut stream) = EventStream::new::<RecommendedWatcher>(Config::default())?;
 
     // Add a path to be watched. All files and directories at that path and
     // below will be monitored for changes.
     watcher.watch(path.as_ref(), RecursiveMode::Recursive)?;
 
-    while let Some(res) = rx.next().await {
+    while let Some(res) = stream.next().await {
         match res {
             Ok(event) => println!("changed: {:?}", event),
             Err(e) => println!("watch error: {:?}", e),
@@ -48,4 +33,4 @@ async fn async_watch<P: AsRef<Path>>(path: P) -> notify::Result<()> {
     }
 
     Ok(())
-}
\ No newline at end of file
+}
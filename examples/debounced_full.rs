@@ -0,0 +1,40 @@
+use std::{path::Path, time::Duration};
+
+use notify::RecursiveMode;
+use notify_debouncer_full::new_debouncer;
+
+/// Example for the full debouncer, keeping event kinds and stitching renames
+fn main() {
+    // emit some events by changing and renaming a file
+    std::thread::spawn(|| {
+        let path = Path::new("test.txt");
+        let renamed = Path::new("test_renamed.txt");
+        let _ = std::fs::remove_file(path);
+        let _ = std::fs::remove_file(renamed);
+        loop {
+            std::fs::write(path, b"Lorem ipsum").unwrap();
+            std::thread::sleep(Duration::from_millis(250));
+            std::fs::rename(path, renamed).unwrap();
+            std::thread::sleep(Duration::from_secs(3));
+        }
+    });
+
+    // setup debouncer
+    let (tx, rx) = std::sync::mpsc::channel();
+
+    // No specific tickrate, max debounce time 2 seconds
+    let mut debouncer = new_debouncer(Duration::from_secs(2), None, tx).unwrap();
+
+    debouncer
+        .watcher()
+        .watch(Path::new("."), RecursiveMode::Recursive)
+        .unwrap();
+
+    // print all events, non returning
+    for result in rx {
+        match result {
+            Ok(events) => events.iter().for_each(|e| println!("{:?}", e.event)),
+            Err(errors) => errors.iter().for_each(|e| println!("{:?}", e)),
+        }
+    }
+}
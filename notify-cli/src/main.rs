@@ -0,0 +1,354 @@
+//! Command line tool printing filesystem events, to debug watch issues
+//!
+//! Run `notify-cli --help` for its options.
+
+mod run;
+
+use notify::{Config, Error, Event, PollWatcher, RecommendedWatcher, RecursiveMode, Watcher};
+use notify_debouncer_mini::{new_debouncer_opt, DebouncedEvent};
+use run::{stop_on_signals, OnBusy, Runner};
+use serde::Serialize;
+use std::ffi::OsString;
+use std::path::PathBuf;
+use std::process;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::Duration;
+
+/// Debounce timeout when running a command, unless `--debounce` is given
+const EXEC_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// How often to check whether a command has exited, to run it for queued changes
+const EXEC_TICK: Duration = Duration::from_millis(100);
+
+const USAGE: &str = "\
+Print the filesystem events of the given paths
+
+USAGE:
+    notify-cli [OPTIONS] <PATH>...
+
+OPTIONS:
+    -n, --non-recursive       Watch only the given directories, not their subdirectories
+        --backend <BACKEND>   Watch with the `native` backend of the platform (default) or `poll`
+        --poll-interval <MS>  Milliseconds between scans of the poll backend [default: 1000]
+        --compare-contents    Let the poll backend compare file contents, not only metadata
+        --json                Print events as JSON Lines instead of text
+        --debounce <MS>       Print at most one event per path every MS milliseconds
+    -x, --exec <COMMAND>      Run COMMAND in a shell for each batch of changes instead of printing
+                              them, with the changed paths in $NOTIFY_PATHS, one per line, and
+                              the first one in $NOTIFY_PATH [debounce default: 500]
+        --on-busy <POLICY>    What to do with changes while COMMAND still runs: `queue` them for
+                              the next run (default), `restart` it or `ignore` them. On unix,
+                              a restart gives COMMAND up to 2s to exit before killing it
+    -1, --exit-after-first    Exit after printing the first event, or after COMMAND ran once
+    -h, --help                Print this help
+";
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum Backend {
+    Native,
+    Poll,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct Options {
+    paths: Vec<PathBuf>,
+    recursive_mode: RecursiveMode,
+    backend: Backend,
+    poll_interval: Duration,
+    compare_contents: bool,
+    json: bool,
+    debounce: Option<Duration>,
+    exec: Option<String>,
+    on_busy: OnBusy,
+    exit_after_first: bool,
+}
+
+/// What the command line asks for
+#[derive(Clone, PartialEq, Eq, Debug)]
+enum Command {
+    Help,
+    Watch(Options),
+}
+
+fn main() {
+    let options = match parse_args(std::env::args_os().skip(1)) {
+        Ok(Command::Watch(options)) => options,
+        Ok(Command::Help) => {
+            print!("{}", USAGE);
+            return;
+        }
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, USAGE);
+            process::exit(2);
+        }
+    };
+    if let Err(e) = run(&options) {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
+
+fn parse_args<I: Iterator<Item = OsString>>(mut args: I) -> Result<Command, String> {
+    let mut options = Options {
+        paths: Vec::new(),
+        recursive_mode: RecursiveMode::Recursive,
+        backend: Backend::Native,
+        poll_interval: Duration::from_secs(1),
+        compare_contents: false,
+        json: false,
+        debounce: None,
+        exec: None,
+        on_busy: OnBusy::Queue,
+        exit_after_first: false,
+    };
+    let mut poll_options = false;
+    let mut on_busy = false;
+    while let Some(arg) = args.next() {
+        match arg.to_str().unwrap_or_default() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-n" | "--non-recursive" => options.recursive_mode = RecursiveMode::NonRecursive,
+            "--backend" => {
+                options.backend = match value(&mut args, "--backend")?.as_str() {
+                    "native" => Backend::Native,
+                    "poll" => Backend::Poll,
+                    other => {
+                        return Err(format!(
+                            "unknown backend '{}', expected 'native' or 'poll'",
+                            other
+                        ))
+                    }
+                }
+            }
+            "--poll-interval" => {
+                options.poll_interval = millis(&mut args, "--poll-interval")?;
+                poll_options = true;
+            }
+            "--compare-contents" => {
+                options.compare_contents = true;
+                poll_options = true;
+            }
+            "--json" => options.json = true,
+            "--debounce" => options.debounce = Some(millis(&mut args, "--debounce")?),
+            "-x" | "--exec" => options.exec = Some(value(&mut args, "--exec")?),
+            "--on-busy" => {
+                options.on_busy = match value(&mut args, "--on-busy")?.as_str() {
+                    "queue" => OnBusy::Queue,
+                    "restart" => OnBusy::Restart,
+                    "ignore" => OnBusy::Ignore,
+                    other => {
+                        return Err(format!(
+                            "unknown policy '{}', expected 'queue', 'restart' or 'ignore'",
+                            other
+                        ))
+                    }
+                };
+                on_busy = true;
+            }
+            "-1" | "--exit-after-first" => options.exit_after_first = true,
+            "--" => options.paths.extend(args.by_ref().map(PathBuf::from)),
+            flag if flag.starts_with('-') && flag != "-" => {
+                return Err(format!("unknown option '{}'", flag))
+            }
+            _ => options.paths.push(arg.into()),
+        }
+    }
+    if options.paths.is_empty() {
+        return Err("no path to watch given".to_string());
+    }
+    if poll_options && options.backend != Backend::Poll {
+        return Err("--poll-interval and --compare-contents need --backend poll".to_string());
+    }
+    if on_busy && options.exec.is_none() {
+        return Err("--on-busy needs --exec".to_string());
+    }
+    if options.exec.is_some() && options.json {
+        return Err("--json can't be used with --exec".to_string());
+    }
+    Ok(Command::Watch(options))
+}
+
+/// Returns the value following the option `name`
+fn value<I: Iterator<Item = OsString>>(args: &mut I, name: &str) -> Result<String, String> {
+    args.next()
+        .and_then(|value| value.into_string().ok())
+        .ok_or_else(|| format!("{} needs a value", name))
+}
+
+/// Returns the number of milliseconds following the option `name`
+fn millis<I: Iterator<Item = OsString>>(args: &mut I, name: &str) -> Result<Duration, String> {
+    let value = value(args, name)?;
+    match value.parse() {
+        Ok(millis) if millis > 0 => Ok(Duration::from_millis(millis)),
+        _ => Err(format!(
+            "{} needs a number of milliseconds, not '{}'",
+            name, value
+        )),
+    }
+}
+
+fn run(options: &Options) -> notify::Result<()> {
+    let config = Config::default()
+        .with_poll_interval(options.poll_interval)
+        .with_compare_contents(options.compare_contents);
+    let timeout = match options.exec {
+        Some(_) => Some(options.debounce.unwrap_or(EXEC_DEBOUNCE)),
+        None => options.debounce,
+    };
+    match (options.backend, timeout) {
+        (Backend::Native, None) => watch::<RecommendedWatcher>(options, config),
+        (Backend::Poll, None) => watch::<PollWatcher>(options, config),
+        (Backend::Native, Some(timeout)) => {
+            debounce::<RecommendedWatcher>(options, config, timeout)
+        }
+        (Backend::Poll, Some(timeout)) => debounce::<PollWatcher>(options, config, timeout),
+    }
+}
+
+/// Print the events of a watcher, until it stops
+fn watch<W: Watcher>(options: &Options, config: Config) -> notify::Result<()> {
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = W::new(tx, config)?;
+    add_watches(&mut watcher, options)?;
+
+    for res in rx {
+        match res {
+            Ok(event) => {
+                print(&event, options.json, format_event);
+                if options.exit_after_first {
+                    break;
+                }
+            }
+            Err(e) => eprintln!("error: {}", e),
+        }
+    }
+    Ok(())
+}
+
+/// Print the events of a debounced watcher, or run the command for them, until it stops
+fn debounce<W: Watcher>(
+    options: &Options,
+    config: Config,
+    timeout: Duration,
+) -> notify::Result<()> {
+    let (tx, rx) = mpsc::channel();
+    let mut debouncer = new_debouncer_opt::<_, W>(timeout, None, tx, config)?;
+    add_watches(debouncer.watcher(), options)?;
+    let mut runner = options
+        .exec
+        .as_ref()
+        .map(|command| Runner::new(command.clone(), options.on_busy));
+    if runner.is_some() {
+        stop_on_signals();
+    }
+
+    loop {
+        let res = match rx.recv_timeout(EXEC_TICK) {
+            Ok(res) => res,
+            Err(RecvTimeoutError::Timeout) => {
+                if let Some(runner) = runner.as_mut() {
+                    runner.tick().map_err(Error::io)?;
+                }
+                continue;
+            }
+            Err(RecvTimeoutError::Disconnected) => return Ok(()),
+        };
+        let events = match res {
+            Ok(events) => events,
+            Err(errors) => {
+                errors.iter().for_each(|e| eprintln!("error: {}", e));
+                continue;
+            }
+        };
+        if let Some(runner) = runner.as_mut() {
+            let paths = events.into_iter().map(|event| event.path).collect();
+            runner.changed(paths).map_err(Error::io)?;
+            if options.exit_after_first {
+                return match runner.wait().map_err(Error::io)? {
+                    Some(status) if !status.success() => {
+                        Err(Error::generic(&format!("command failed: {}", status)))
+                    }
+                    _ => Ok(()),
+                };
+            }
+            continue;
+        }
+        for event in events {
+            print(&event, options.json, format_debounced_event);
+            if options.exit_after_first {
+                return Ok(());
+            }
+        }
+    }
+}
+
+fn add_watches(watcher: &mut dyn Watcher, options: &Options) -> notify::Result<()> {
+    for path in &options.paths {
+        watcher.watch(path, options.recursive_mode).map_err(|e| {
+            if e.paths.is_empty() {
+                e.add_path(path.clone())
+            } else {
+                e
+            }
+        })?;
+    }
+    Ok(())
+}
+
+/// Print `event` as a line of JSON, or of text formatted by `text`
+fn print<T: Serialize>(event: &T, json: bool, text: fn(&T) -> String) {
+    if !json {
+        println!("{}", text(event));
+        return;
+    }
+    match serde_json::to_string(event) {
+        Ok(line) => println!("{}", line),
+        // paths that aren't UTF-8 can't be serialized
+        Err(e) => eprintln!("error: {}", Error::generic(&e.to_string())),
+    }
+}
+
+fn format_event(event: &Event) -> String {
+    let mut line = format!("{:?}", event.kind);
+    for path in &event.paths {
+        line.push(' ');
+        line.push_str(&path.display().to_string());
+    }
+    line
+}
+
+fn format_debounced_event(event: &DebouncedEvent) -> String {
+    format!("{:?} {}", event.kind, event.path.display())
+}
+
+#[test]
+fn parses_options() {
+    let args = |args: &[&str]| parse_args(args.iter().map(OsString::from));
+
+    let command = args(&[
+        "--json",
+        "-n",
+        "--backend",
+        "poll",
+        "--poll-interval",
+        "250",
+        "a",
+        "b",
+    ]);
+    let options = match command {
+        Ok(Command::Watch(options)) => options,
+        other => panic!("unexpected {:?}", other),
+    };
+    assert_eq!(options.paths, [PathBuf::from("a"), PathBuf::from("b")]);
+    assert_eq!(options.recursive_mode, RecursiveMode::NonRecursive);
+    assert_eq!(options.backend, Backend::Poll);
+    assert_eq!(options.poll_interval, Duration::from_millis(250));
+    assert!(options.json);
+
+    assert_eq!(args(&["a", "--help"]), Ok(Command::Help));
+    assert!(args(&[]).is_err());
+    assert!(args(&["--compare-contents", "a"]).is_err());
+    assert!(args(&["--debounce", "soon", "a"]).is_err());
+    assert!(args(&["--verbose", "a"]).is_err());
+    assert!(args(&["--on-busy", "restart", "a"]).is_err());
+    assert!(args(&["-x", "make", "--on-busy", "later", "a"]).is_err());
+}
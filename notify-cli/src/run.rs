@@ -0,0 +1,267 @@
+//! Running a command on change, see `--exec`
+
+use std::collections::BTreeSet;
+use std::ffi::OsString;
+use std::io;
+#[cfg(unix)]
+use std::os::raw::c_int;
+use std::path::PathBuf;
+use std::process::{Child, Command, ExitStatus};
+#[cfg(unix)]
+use std::sync::atomic::{AtomicI32, Ordering};
+#[cfg(unix)]
+use std::thread;
+#[cfg(unix)]
+use std::time::{Duration, Instant};
+
+/// How long a restarted command may take to exit before it is killed
+#[cfg(unix)]
+const KILL_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Process group of the running command, for the signal handler to stop it
+#[cfg(unix)]
+static RUNNING_GROUP: AtomicI32 = AtomicI32::new(0);
+
+/// What to do with changes arriving while the command runs
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum OnBusy {
+    /// Run the command again once it exits, for all paths changed in the meantime
+    Queue,
+
+    /// Stop the command and run it again for the new changes
+    Restart,
+
+    /// Drop the changes
+    Ignore,
+}
+
+/// Runs a shell command for batches of changed paths
+///
+/// The command gets the changed paths in `NOTIFY_PATHS`, one per line, and the first of them in
+/// `NOTIFY_PATH`. On unix it runs in its own process group, so restarting stops its children too.
+/// That group doesn't get the Ctrl-C of the terminal, see [`stop_on_signals`].
+#[derive(Debug)]
+pub struct Runner {
+    command: String,
+    on_busy: OnBusy,
+    child: Option<Child>,
+    /// Paths changed while the command ran, with [`OnBusy::Queue`]
+    queued: BTreeSet<PathBuf>,
+}
+
+impl Runner {
+    pub fn new(command: String, on_busy: OnBusy) -> Self {
+        Runner {
+            command,
+            on_busy,
+            child: None,
+            queued: BTreeSet::new(),
+        }
+    }
+
+    /// Run the command for `paths`, or deal with them according to the [`OnBusy`] policy
+    pub fn changed(&mut self, paths: Vec<PathBuf>) -> io::Result<()> {
+        self.reap()?;
+        if self.child.is_some() {
+            match self.on_busy {
+                OnBusy::Queue => {
+                    self.queued.extend(paths);
+                    return Ok(());
+                }
+                OnBusy::Ignore => return Ok(()),
+                OnBusy::Restart => self.stop()?,
+            }
+        }
+        // paths queued while the command ran, if it exited since the last tick
+        self.queued.extend(paths);
+        let paths = std::mem::take(&mut self.queued);
+        self.spawn(paths)
+    }
+
+    /// Run the command for the queued paths, if it has exited since they changed
+    pub fn tick(&mut self) -> io::Result<()> {
+        self.reap()?;
+        if self.child.is_none() && !self.queued.is_empty() {
+            let paths = std::mem::take(&mut self.queued);
+            self.spawn(paths)?;
+        }
+        Ok(())
+    }
+
+    /// Wait for the running command to exit, returning its status if one was running
+    pub fn wait(&mut self) -> io::Result<Option<ExitStatus>> {
+        let child = self.child.take();
+        #[cfg(unix)]
+        RUNNING_GROUP.store(0, Ordering::SeqCst);
+        child.map(|mut child| child.wait()).transpose()
+    }
+
+    /// Forget the command if it has exited
+    fn reap(&mut self) -> io::Result<()> {
+        if let Some(child) = self.child.as_mut() {
+            if let Some(status) = child.try_wait()? {
+                if !status.success() {
+                    eprintln!("command failed: {}", status);
+                }
+                self.child = None;
+                #[cfg(unix)]
+                RUNNING_GROUP.store(0, Ordering::SeqCst);
+            }
+        }
+        Ok(())
+    }
+
+    fn spawn(&mut self, paths: BTreeSet<PathBuf>) -> io::Result<()> {
+        let mut all = OsString::new();
+        for (i, path) in paths.iter().enumerate() {
+            if i > 0 {
+                all.push("\n");
+            }
+            all.push(path);
+        }
+        let mut command = shell(&self.command);
+        command.env("NOTIFY_PATHS", all);
+        if let Some(path) = paths.iter().next() {
+            command.env("NOTIFY_PATH", path);
+        }
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+            // a process group of its own, to stop whatever the command starts on restart
+            unsafe {
+                command.pre_exec(|| {
+                    if libc::setpgid(0, 0) == 0 {
+                        Ok(())
+                    } else {
+                        Err(io::Error::last_os_error())
+                    }
+                });
+            }
+        }
+        let child = command.spawn()?;
+        #[cfg(unix)]
+        {
+            // here as well, so the group exists even if the child didn't get to `pre_exec` yet.
+            // Fails once the child has exec'd, after which it is set already.
+            let pid = child.id() as libc::pid_t;
+            unsafe { libc::setpgid(pid, pid) };
+            RUNNING_GROUP.store(pid, Ordering::SeqCst);
+        }
+        self.child = Some(child);
+        Ok(())
+    }
+
+    /// Stop the running command, asking it to terminate first on unix
+    fn stop(&mut self) -> io::Result<()> {
+        let mut child = match self.child.take() {
+            Some(child) => child,
+            None => return Ok(()),
+        };
+        #[cfg(unix)]
+        {
+            RUNNING_GROUP.store(0, Ordering::SeqCst);
+            let group = -(child.id() as libc::pid_t);
+            unsafe { libc::kill(group, libc::SIGTERM) };
+            let start = Instant::now();
+            while start.elapsed() < KILL_TIMEOUT {
+                if child.try_wait()?.is_some() {
+                    return Ok(());
+                }
+                thread::sleep(Duration::from_millis(10));
+            }
+            unsafe { libc::kill(group, libc::SIGKILL) };
+        }
+        #[cfg(not(unix))]
+        child.kill()?;
+        child.wait().map(|_| ())
+    }
+}
+
+impl Drop for Runner {
+    fn drop(&mut self) {
+        let _ = self.stop();
+    }
+}
+
+/// Stop the running command when notify-cli gets SIGINT or SIGTERM, then exit by that signal
+///
+/// The command runs in a process group of its own, which a Ctrl-C in the terminal doesn't reach.
+#[cfg(unix)]
+pub fn stop_on_signals() {
+    extern "C" fn handle(signal: c_int) {
+        let group = RUNNING_GROUP.load(Ordering::SeqCst);
+        // only async-signal-safe calls here
+        unsafe {
+            if group != 0 {
+                libc::kill(-group, libc::SIGTERM);
+            }
+            libc::signal(signal, libc::SIG_DFL);
+            libc::raise(signal);
+        }
+    }
+    for signal in [libc::SIGINT, libc::SIGTERM] {
+        let handle = handle as extern "C" fn(c_int);
+        unsafe { libc::signal(signal, handle as libc::sighandler_t) };
+    }
+}
+
+/// The command gets the Ctrl-C of the console along with notify-cli on windows
+#[cfg(not(unix))]
+pub fn stop_on_signals() {}
+
+#[cfg(unix)]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell.arg("-c").arg(command);
+    shell
+}
+
+#[cfg(windows)]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.arg("/C").arg(command);
+    shell
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn runs(on_busy: OnBusy) -> String {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("log");
+        let command = format!("sleep 0.3; echo \"$NOTIFY_PATHS\" >> {}", log.display());
+        let mut runner = Runner::new(command, on_busy);
+        runner.changed(vec!["a".into()]).unwrap();
+        runner.changed(vec!["c".into()]).unwrap();
+        runner.changed(vec!["b".into()]).unwrap();
+        while runner.child.is_some() || !runner.queued.is_empty() {
+            runner.tick().unwrap();
+            thread::sleep(Duration::from_millis(20));
+        }
+        fs::read_to_string(log).unwrap_or_default()
+    }
+
+    #[test]
+    fn queued_changes_run_with_new_ones() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("log");
+        let command = format!("sleep 0.1; echo \"$NOTIFY_PATHS\" >> {}", log.display());
+        let mut runner = Runner::new(command, OnBusy::Queue);
+        runner.changed(vec!["a".into()]).unwrap();
+        runner.changed(vec!["b".into()]).unwrap();
+        // the command exits before the next tick runs it for the queued path
+        thread::sleep(Duration::from_millis(500));
+        runner.changed(vec!["c".into()]).unwrap();
+        runner.wait().unwrap();
+        assert_eq!(fs::read_to_string(log).unwrap(), "a\nb\nc\n");
+    }
+
+    #[test]
+    fn on_busy_policies() {
+        assert_eq!(runs(OnBusy::Queue), "a\nb\nc\n");
+        assert_eq!(runs(OnBusy::Ignore), "a\n");
+        assert_eq!(runs(OnBusy::Restart), "b\n");
+    }
+}
@@ -0,0 +1,679 @@
+//! A debouncer for [notify] that keeps the event kind.
+//!
+//! In contrast to [notify-debouncer-mini](https://docs.rs/notify-debouncer-mini), which only
+//! reports that something happened to a path, this debouncer collapses all events of a path
+//! inside one timeframe into their net result:
+//!
+//! - a create followed by modifications is emitted as the create
+//! - a create followed by a remove emits nothing at all
+//! - a remove followed by a create is emitted as a modification
+//! - `RenameMode::From` and `RenameMode::To` events sharing a tracker are stitched into a single
+//!   `RenameMode::Both` event with both paths
+//!
+//! # Installation
+//!
+//! ```toml
+//! [dependencies]
+//! notify-debouncer-full = "0.1.0"
+//! ```
+//! In case you want to select specific features of notify,
+//! specify notify as dependency explicitely in your dependencies.
+//! Otherwise you can just use the re-export of notify from debouncer-full.
+//! ```toml
+//! notify-debouncer-full = "0.1.0"
+//! notify = { version = "..", features = [".."] }
+//! ```
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! # use std::path::Path;
+//! # use std::time::Duration;
+//! use notify_debouncer_full::{notify::*, new_debouncer, DebounceEventResult};
+//!
+//! # fn main() {
+//!     // Select recommended watcher for debouncer.
+//!     // Using a callback here, could also be a channel.
+//!     let mut debouncer = new_debouncer(Duration::from_secs(2), None, |res: DebounceEventResult| {
+//!         match res {
+//!             Ok(events) => events.iter().for_each(|e| println!("Event {:?} for {:?}", e.kind, e.paths)),
+//!             Err(errors) => errors.iter().for_each(|e| println!("Error {:?}", e)),
+//!         }
+//!     }).unwrap();
+//!
+//!     // Add a path to be watched. All files and directories at that path and
+//!     // below will be monitored for changes.
+//!     debouncer.watcher().watch(Path::new("."), RecursiveMode::Recursive).unwrap();
+//! # }
+//! ```
+//!
+//! # Features
+//!
+//! The following crate features can be turned on or off in your cargo dependency config:
+//!
+//! - `crossbeam` enabled by default, adds [`DebounceEventHandler`](DebounceEventHandler) support for crossbeam channels.
+//!   Also enables crossbeam-channel in the re-exported notify. You may want to disable this when using the tokio async runtime.
+use std::{
+    collections::HashMap,
+    ops::{Deref, DerefMut},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+pub use notify;
+use notify::{
+    event::{DataChange, ModifyKind, RenameMode},
+    Error, ErrorKind, Event, EventKind, RecommendedWatcher, Watcher,
+};
+
+/// The set of requirements for watcher debounce event handling functions.
+///
+/// # Example implementation
+///
+/// ```rust,no_run
+/// # use notify::{Event, Result, EventHandler};
+/// # use notify_debouncer_full::{DebounceEventHandler, DebounceEventResult};
+///
+/// /// Prints received events
+/// struct EventPrinter;
+///
+/// impl DebounceEventHandler for EventPrinter {
+///     fn handle_event(&mut self, event: DebounceEventResult) {
+///         match event {
+///             Ok(events) => {
+///                 for event in events {
+///                     println!("Event {:?} for paths {:?}", event.kind, event.paths);
+///                 }
+///             },
+///             // errors are batched, so you get either events or errors, probably both per debounce tick (two calls)
+///             Err(errors) => errors.iter().for_each(|e| println!("Got error {:?}", e)),
+///         }
+///     }
+/// }
+/// ```
+pub trait DebounceEventHandler: Send + 'static {
+    /// Handles an event.
+    fn handle_event(&mut self, event: DebounceEventResult);
+}
+
+impl<F> DebounceEventHandler for F
+where
+    F: FnMut(DebounceEventResult) + Send + 'static,
+{
+    fn handle_event(&mut self, event: DebounceEventResult) {
+        (self)(event);
+    }
+}
+
+#[cfg(feature = "crossbeam")]
+impl DebounceEventHandler for crossbeam_channel::Sender<DebounceEventResult> {
+    fn handle_event(&mut self, event: DebounceEventResult) {
+        let _ = self.send(event);
+    }
+}
+
+impl DebounceEventHandler for std::sync::mpsc::Sender<DebounceEventResult> {
+    fn handle_event(&mut self, event: DebounceEventResult) {
+        let _ = self.send(event);
+    }
+}
+
+/// A result of debounced events.
+/// Comes with either a vec of events or vec of errors.
+pub type DebounceEventResult = Result<Vec<DebouncedEvent>, Vec<Error>>;
+
+/// A debounced event.
+///
+/// Wraps the collapsed notify [`Event`] together with the time the first event of its
+/// timeframe was received. Derefs to the inner [`Event`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DebouncedEvent {
+    /// The collapsed event
+    pub event: Event,
+    /// Time the first event of this timeframe was received
+    pub time: Instant,
+}
+
+impl DebouncedEvent {
+    fn new(event: Event, time: Instant) -> Self {
+        Self { event, time }
+    }
+}
+
+impl Deref for DebouncedEvent {
+    type Target = Event;
+
+    fn deref(&self) -> &Self::Target {
+        &self.event
+    }
+}
+
+impl DerefMut for DebouncedEvent {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.event
+    }
+}
+
+/// Collapsed state of a single path inside the current timeframe
+struct EventData {
+    /// Original path, if the path was renamed into place during this timeframe
+    from: Option<PathBuf>,
+    /// Net event kind of the path, not counting the rename
+    kind: Option<EventKind>,
+    /// Tracker of the rename, if any
+    tracker: Option<usize>,
+    /// Insertion Time
+    insert: Instant,
+    /// Last Update
+    update: Instant,
+}
+
+impl EventData {
+    fn new(time: Instant) -> Self {
+        Self {
+            from: None,
+            kind: None,
+            tracker: None,
+            insert: time,
+            update: time,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.from.is_none() && self.kind.is_none()
+    }
+}
+
+type DebounceData = Arc<Mutex<DebounceDataInner>>;
+
+#[derive(Default)]
+struct DebounceDataInner {
+    d: HashMap<PathBuf, EventData>,
+    /// Rename sources waiting for their counterpart, by tracker
+    rename_from: HashMap<Option<usize>, (PathBuf, Instant)>,
+    /// Trackers of already stitched renames, used to drop the trailing `RenameMode::Both` event
+    stitched: HashMap<usize, Instant>,
+    /// Events without paths or flagged for a rescan, passed on as they are
+    passthrough: Vec<DebouncedEvent>,
+    timeout: Duration,
+    e: Vec<crate::Error>,
+}
+
+impl DebounceDataInner {
+    /// Retrieve a vec of debounced events, removing them from the cache
+    pub fn debounced_events(&mut self) -> Vec<DebouncedEvent> {
+        let timeout = self.timeout;
+
+        // a rename source without counterpart was moved out of the watched tree
+        let expired_from: Vec<_> = self
+            .rename_from
+            .iter()
+            .filter(|(_, (_, time))| time.elapsed() >= timeout)
+            .map(|(tracker, _)| *tracker)
+            .collect();
+        for tracker in expired_from {
+            if let Some((path, time)) = self.rename_from.remove(&tracker) {
+                self.collapse(
+                    path,
+                    EventKind::Remove(notify::event::RemoveKind::Any),
+                    time,
+                );
+            }
+        }
+        self.stitched.retain(|_, time| time.elapsed() < timeout);
+
+        let mut events_expired = std::mem::take(&mut self.passthrough);
+        let mut data_expired = Vec::with_capacity(self.d.len());
+        let mut data_back = HashMap::with_capacity(self.d.len());
+        // TODO: perfect fit for drain_filter https://github.com/rust-lang/rust/issues/59618
+        for (k, v) in self.d.drain() {
+            if v.update.elapsed() >= timeout || v.insert.elapsed() >= timeout {
+                data_expired.push((k, v));
+            } else {
+                data_back.insert(k, v);
+            }
+        }
+        self.d = data_back;
+
+        data_expired.sort_by(|(a_path, a), (b_path, b)| {
+            a.insert.cmp(&b.insert).then_with(|| a_path.cmp(b_path))
+        });
+        for (path, data) in data_expired {
+            if let Some(from) = data.from {
+                let mut event = Event::new(EventKind::Modify(ModifyKind::Name(RenameMode::Both)))
+                    .add_path(from)
+                    .add_path(path.clone());
+                if let Some(tracker) = data.tracker {
+                    event = event.set_tracker(tracker);
+                }
+                events_expired.push(DebouncedEvent::new(event, data.insert));
+            }
+            if let Some(kind) = data.kind {
+                events_expired.push(DebouncedEvent::new(
+                    Event::new(kind).add_path(path),
+                    data.insert,
+                ));
+            }
+        }
+        events_expired
+    }
+
+    /// Returns all currently stored errors
+    pub fn errors(&mut self) -> Vec<Error> {
+        std::mem::take(&mut self.e)
+    }
+
+    /// Add an error entry to re-send later on
+    pub fn add_error(&mut self, e: crate::Error) {
+        self.e.push(e);
+    }
+
+    /// Add new event to debouncer cache
+    pub fn add_event(&mut self, e: Event) {
+        let now = Instant::now();
+
+        if e.need_rescan() || e.paths.is_empty() {
+            self.passthrough.push(DebouncedEvent::new(e, now));
+            return;
+        }
+
+        match e.kind {
+            EventKind::Modify(ModifyKind::Name(RenameMode::From)) => {
+                let tracker = e.tracker();
+                if let Some(path) = e.paths.into_iter().next() {
+                    // an older source with the same tracker can't be paired anymore
+                    if let Some((old, time)) = self.rename_from.insert(tracker, (path, now)) {
+                        self.collapse(old, EventKind::Remove(notify::event::RemoveKind::Any), time);
+                    }
+                }
+            }
+            EventKind::Modify(ModifyKind::Name(RenameMode::To)) => {
+                let tracker = e.tracker();
+                let to = match e.paths.into_iter().next() {
+                    Some(to) => to,
+                    None => return,
+                };
+                match self.rename_from.remove(&tracker) {
+                    Some((from, _)) => {
+                        if let Some(tracker) = tracker {
+                            self.stitched.insert(tracker, now);
+                        }
+                        self.rename(from, to, tracker, now);
+                    }
+                    None => {
+                        self.collapse(to, EventKind::Create(notify::event::CreateKind::Any), now)
+                    }
+                }
+            }
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)) if e.paths.len() == 2 => {
+                let tracker = e.tracker();
+                if let Some(tracker) = tracker {
+                    if self.stitched.contains_key(&tracker) {
+                        return;
+                    }
+                }
+                let mut paths = e.paths.into_iter();
+                if let (Some(from), Some(to)) = (paths.next(), paths.next()) {
+                    self.rename(from, to, tracker, now);
+                }
+            }
+            kind => {
+                for path in e.paths {
+                    self.collapse(path, kind.clone(), now);
+                }
+            }
+        }
+    }
+
+    /// Merge a new event kind for a path into the cache
+    fn collapse(&mut self, path: PathBuf, kind: EventKind, time: Instant) {
+        let mut data = self.d.remove(&path).unwrap_or_else(|| EventData::new(time));
+        data.update = time;
+
+        if kind.is_remove() {
+            if let Some(from) = data.from.take() {
+                // renamed into place and removed again: only the origin is gone
+                data.tracker = None;
+                if !matches!(data.kind, Some(EventKind::Create(_))) {
+                    self.collapse(from, kind, time);
+                }
+                return;
+            }
+        }
+
+        data.kind = match data.kind.take() {
+            None => Some(kind),
+            Some(old) => collapse_kinds(old, kind),
+        };
+
+        if !data.is_empty() {
+            self.d.insert(path, data);
+        }
+    }
+
+    /// Move the cached state of `from` (and everything below it) to `to`
+    fn rename(&mut self, from: PathBuf, to: PathBuf, tracker: Option<usize>, time: Instant) {
+        let source = self.d.remove(&from);
+
+        // re-root pending entries of a moved directory
+        let children: Vec<PathBuf> = self
+            .d
+            .keys()
+            .filter(|path| path.starts_with(&from))
+            .cloned()
+            .collect();
+        for child in children {
+            if let (Some(data), Ok(rel)) = (self.d.remove(&child), child.strip_prefix(&from)) {
+                self.d.insert(to.join(rel), data);
+            }
+        }
+
+        let (origin, kind, insert) = match source {
+            Some(source) => (
+                source.from.unwrap_or_else(|| from.clone()),
+                source.kind,
+                source.insert,
+            ),
+            None => (from, None, time),
+        };
+
+        if let Some(kind @ EventKind::Create(_)) = kind {
+            // created and renamed inside the timeframe: only the create is of interest
+            self.collapse(to, kind, time);
+            return;
+        }
+
+        let mut data = self.d.remove(&to).unwrap_or_else(|| EventData::new(insert));
+        data.insert = data.insert.min(insert);
+        data.update = time;
+        data.kind = kind;
+        if origin == to {
+            // renamed back to where it started
+            data.from = None;
+            data.tracker = None;
+        } else {
+            data.from = Some(origin);
+            data.tracker = tracker;
+        }
+
+        if !data.is_empty() {
+            self.d.insert(to, data);
+        }
+    }
+}
+
+/// Net event kind of two events following each other on the same path
+fn collapse_kinds(old: EventKind, new: EventKind) -> Option<EventKind> {
+    match (old, new) {
+        // never existed as far as the consumer is concerned
+        (EventKind::Create(_), EventKind::Remove(_)) => None,
+        (old @ EventKind::Create(_), _) => Some(old),
+        // replaced, e.g. by an editor saving a new file in place
+        (EventKind::Remove(_), EventKind::Create(_)) => Some(EventKind::Modify(ModifyKind::Any)),
+        (_, new @ EventKind::Remove(_)) => Some(new),
+        (old, EventKind::Access(_)) if !old.is_access() => Some(old),
+        (EventKind::Modify(old), EventKind::Modify(new)) => {
+            Some(EventKind::Modify(collapse_modify_kinds(old, new)))
+        }
+        (_, new) => Some(new),
+    }
+}
+
+fn collapse_modify_kinds(old: ModifyKind, new: ModifyKind) -> ModifyKind {
+    match (old, new) {
+        (old, new) if old == new => old,
+        (ModifyKind::Data(_), ModifyKind::Data(_)) => ModifyKind::Data(DataChange::Any),
+        (ModifyKind::Metadata(_), ModifyKind::Metadata(_)) => {
+            ModifyKind::Metadata(notify::event::MetadataKind::Any)
+        }
+        _ => ModifyKind::Any,
+    }
+}
+
+/// Debouncer guard, stops the debouncer on drop
+pub struct Debouncer<T: Watcher> {
+    stop: Arc<AtomicBool>,
+    watcher: T,
+    debouncer_thread: Option<std::thread::JoinHandle<()>>,
+}
+
+impl<T: Watcher> Debouncer<T> {
+    /// Stop the debouncer, waits for the event thread to finish.
+    /// May block for the duration of one tick_rate.
+    pub fn stop(mut self) {
+        self.set_stop();
+        if let Some(t) = self.debouncer_thread.take() {
+            let _ = t.join();
+        }
+    }
+
+    /// Stop the debouncer, does not wait for the event thread to finish.
+    pub fn stop_nonblocking(self) {
+        self.set_stop();
+    }
+
+    fn set_stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    /// Access to the internally used notify Watcher backend
+    pub fn watcher(&mut self) -> &mut dyn Watcher {
+        &mut self.watcher
+    }
+}
+
+impl<T: Watcher> Drop for Debouncer<T> {
+    fn drop(&mut self) {
+        // don't imitate c++ async futures and block on drop
+        self.set_stop();
+    }
+}
+
+/// Creates a new debounced watcher with custom configuration.
+///
+/// Timeout is the amount of time after which the collapsed events of a path are emitted, also if there still are events incoming for the specific path.
+///
+/// If tick_rate is None, notify will select a tick rate that is less than the provided timeout.
+pub fn new_debouncer_opt<F: DebounceEventHandler, T: Watcher>(
+    timeout: Duration,
+    tick_rate: Option<Duration>,
+    mut event_handler: F,
+    config: notify::Config,
+) -> Result<Debouncer<T>, Error> {
+    let data = DebounceData::default();
+
+    let stop = Arc::new(AtomicBool::new(false));
+
+    let tick_div = 4;
+    let tick = match tick_rate {
+        Some(v) => {
+            if v > timeout {
+                return Err(Error::new(ErrorKind::Generic(format!(
+                    "Invalid tick_rate, tick rate {:?} > {:?} timeout!",
+                    v, timeout
+                ))));
+            }
+            v
+        }
+        None => timeout.checked_div(tick_div).ok_or_else(|| {
+            Error::new(ErrorKind::Generic(format!(
+                "Failed to calculate tick as {:?}/{}!",
+                timeout, tick_div
+            )))
+        })?,
+    };
+
+    {
+        let mut data_w = data.lock().unwrap();
+        data_w.timeout = timeout;
+    }
+
+    let data_c = data.clone();
+    let stop_c = stop.clone();
+    let thread = std::thread::Builder::new()
+        .name("notify-rs debouncer loop".to_string())
+        .spawn(move || loop {
+            if stop_c.load(Ordering::Acquire) {
+                break;
+            }
+            std::thread::sleep(tick);
+            let send_data;
+            let errors: Vec<crate::Error>;
+            {
+                let mut lock = data_c.lock().expect("Can't lock debouncer data!");
+                send_data = lock.debounced_events();
+                errors = lock.errors();
+            }
+            if !send_data.is_empty() {
+                event_handler.handle_event(Ok(send_data));
+            }
+            if !errors.is_empty() {
+                event_handler.handle_event(Err(errors));
+            }
+        })?;
+
+    let watcher = T::new(
+        move |e: Result<Event, Error>| {
+            let mut lock = data.lock().expect("Can't lock debouncer data!");
+
+            match e {
+                Ok(e) => lock.add_event(e),
+                // can't have multiple TX, so we need to pipe that through our debouncer
+                Err(e) => lock.add_error(e),
+            }
+        },
+        config,
+    )?;
+
+    let guard = Debouncer {
+        watcher,
+        debouncer_thread: Some(thread),
+        stop,
+    };
+
+    Ok(guard)
+}
+
+/// Short function to create a new debounced watcher with the recommended debouncer.
+///
+/// Timeout is the amount of time after which the collapsed events of a path are emitted, also if there still are events incoming for the specific path.
+///
+/// If tick_rate is None, notify will select a tick rate that is less than the provided timeout.
+pub fn new_debouncer<F: DebounceEventHandler>(
+    timeout: Duration,
+    tick_rate: Option<Duration>,
+    event_handler: F,
+) -> Result<Debouncer<RecommendedWatcher>, Error> {
+    new_debouncer_opt::<F, RecommendedWatcher>(
+        timeout,
+        tick_rate,
+        event_handler,
+        notify::Config::default(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::event::{CreateKind, RemoveKind};
+    use std::path::Path;
+
+    fn path(p: &str) -> PathBuf {
+        Path::new("/watched").join(p)
+    }
+
+    fn flush(data: &mut DebounceDataInner) -> Vec<Event> {
+        data.debounced_events()
+            .into_iter()
+            .map(|e| e.event)
+            .collect()
+    }
+
+    #[test]
+    fn create_modify_collapses_to_create() {
+        let mut data = DebounceDataInner::default();
+        data.add_event(Event::new(EventKind::Create(CreateKind::File)).add_path(path("a")));
+        data.add_event(
+            Event::new(EventKind::Modify(ModifyKind::Data(DataChange::Any))).add_path(path("a")),
+        );
+
+        assert_eq!(
+            flush(&mut data),
+            vec![Event::new(EventKind::Create(CreateKind::File)).add_path(path("a"))]
+        );
+    }
+
+    #[test]
+    fn create_remove_emits_nothing() {
+        let mut data = DebounceDataInner::default();
+        data.add_event(Event::new(EventKind::Create(CreateKind::File)).add_path(path("a")));
+        data.add_event(Event::new(EventKind::Remove(RemoveKind::File)).add_path(path("a")));
+
+        assert!(flush(&mut data).is_empty());
+    }
+
+    #[test]
+    fn inotify_rename_is_stitched() {
+        let mut data = DebounceDataInner::default();
+        let name = |mode| EventKind::Modify(ModifyKind::Name(mode));
+        data.add_event(
+            Event::new(name(RenameMode::From))
+                .add_path(path("a"))
+                .set_tracker(7),
+        );
+        data.add_event(
+            Event::new(name(RenameMode::To))
+                .add_path(path("b"))
+                .set_tracker(7),
+        );
+        data.add_event(
+            Event::new(name(RenameMode::Both))
+                .add_path(path("a"))
+                .add_path(path("b"))
+                .set_tracker(7),
+        );
+
+        assert_eq!(
+            flush(&mut data),
+            vec![Event::new(name(RenameMode::Both))
+                .add_path(path("a"))
+                .add_path(path("b"))
+                .set_tracker(7)]
+        );
+    }
+
+    #[test]
+    fn unpaired_rename_source_is_a_remove() {
+        let mut data = DebounceDataInner::default();
+        data.add_event(
+            Event::new(EventKind::Modify(ModifyKind::Name(RenameMode::From)))
+                .add_path(path("a"))
+                .set_tracker(3),
+        );
+
+        assert_eq!(
+            flush(&mut data),
+            vec![Event::new(EventKind::Remove(RemoveKind::Any)).add_path(path("a"))]
+        );
+    }
+
+    #[test]
+    fn created_then_renamed_is_a_create() {
+        let mut data = DebounceDataInner::default();
+        data.add_event(Event::new(EventKind::Create(CreateKind::File)).add_path(path("a.tmp")));
+        data.add_event(
+            Event::new(EventKind::Modify(ModifyKind::Name(RenameMode::Both)))
+                .add_path(path("a.tmp"))
+                .add_path(path("a")),
+        );
+
+        assert_eq!(
+            flush(&mut data),
+            vec![Event::new(EventKind::Create(CreateKind::File)).add_path(path("a"))]
+        );
+    }
+}
@@ -1,5 +1,13 @@
 //! Configuration types
 
+use crate::event::{AccessKind, AccessMode, EventKind, ModifyKind};
+#[cfg(feature = "replay")]
+use crate::replay::ReplayPace;
+use crate::HashStrategy;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::Duration;
 
 /// Indicates whether only the provided directory or its sub-directories as well should be watched
@@ -12,6 +20,48 @@ pub enum RecursiveMode {
     NonRecursive,
 }
 
+/// When [crate::PollWatcher] scans, see [Config::with_poll_schedule]
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub enum PollSchedule {
+    /// Wait the poll interval after each scan, so scans are further apart the longer they take
+    FixedDelay,
+
+    /// Start a scan every poll interval, right after the last one if that took longer
+    FixedRate,
+
+    /// Wait the poll interval after a scan finding changes, and twice as long as the last time
+    /// after a scan finding none, up to `max_interval`
+    Adaptive {
+        /// Longest wait between scans of an idle tree
+        max_interval: Duration,
+    },
+}
+
+impl Default for PollSchedule {
+    fn default() -> Self {
+        PollSchedule::FixedDelay
+    }
+}
+
+/// How recursive watches treat symbolic links, see [Config::with_symlink_policy]
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub enum SymlinkPolicy {
+    /// Watch links themselves, never what they point to
+    DontFollow,
+
+    /// Watch what links point to, wherever that is
+    Follow,
+
+    /// Watch what links point to inside the watched path, and other links themselves
+    FollowWithinRoot,
+}
+
+impl Default for SymlinkPolicy {
+    fn default() -> Self {
+        SymlinkPolicy::Follow
+    }
+}
+
 impl RecursiveMode {
     pub(crate) fn is_recursive(&self) -> bool {
         match *self {
@@ -21,6 +71,57 @@ impl RecursiveMode {
     }
 }
 
+/// How a single path is watched, see [crate::Watcher::watch_with]
+///
+/// ```rust
+/// # use notify::{EventKindMask, RecursiveMode, WatchOptions};
+/// let options = WatchOptions::new(RecursiveMode::Recursive)
+///     .with_event_kinds(EventKindMask::CREATE | EventKindMask::REMOVE);
+/// ```
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub struct WatchOptions {
+    /// See [WatchOptions::new]
+    recursive_mode: RecursiveMode,
+
+    /// See [WatchOptions::with_event_kinds]
+    event_kinds: Option<EventKindMask>,
+}
+
+impl WatchOptions {
+    /// Watch with `recursive_mode`, see [crate::Watcher::watch]
+    pub fn new(recursive_mode: RecursiveMode) -> Self {
+        Self {
+            recursive_mode,
+            event_kinds: None,
+        }
+    }
+
+    /// Returns current setting
+    pub fn recursive_mode(&self) -> RecursiveMode {
+        self.recursive_mode
+    }
+
+    /// Classes of events to deliver for the path, see [EventKindMask]
+    ///
+    /// Defaults to the classes of the [Config] for [crate::INotifyWatcher] and to all events for
+    /// other watchers.
+    pub fn with_event_kinds(mut self, event_kinds: EventKindMask) -> Self {
+        self.event_kinds = Some(event_kinds);
+        self
+    }
+
+    /// Returns current setting
+    pub fn event_kinds(&self) -> Option<EventKindMask> {
+        self.event_kinds
+    }
+}
+
+impl From<RecursiveMode> for WatchOptions {
+    fn from(recursive_mode: RecursiveMode) -> Self {
+        Self::new(recursive_mode)
+    }
+}
+
 /// Watcher Backend configuration
 /// 
 /// This contains multiple settings that may relate to only one specific backend,
@@ -35,13 +136,61 @@ impl RecursiveMode {
 /// ```
 /// 
 /// Some options can be changed during runtime, others have to be set when creating the watcher backend.
-#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
 pub struct Config {
     /// See [BackendConfig::with_poll_interval]
     poll_interval: Duration,
 
+    /// See [Config::with_poll_schedule]
+    poll_schedule: PollSchedule,
+
     /// See [BackendConfig::with_compare_contents]
     compare_contents: bool,
+
+    /// See [Config::with_hash_strategy]
+    hash_strategy: Option<HashStrategy>,
+
+    /// See [Config::with_pending_watches]
+    pending_watches: bool,
+
+    /// See [Config::with_ignore]
+    ignore: Option<IgnoreRules>,
+
+    /// See [Config::with_event_kinds]
+    event_kinds: EventKindMask,
+
+    /// See [Config::with_rename_window]
+    rename_window: Duration,
+
+    /// See [Config::with_overflow_recovery]
+    overflow_recovery: bool,
+
+    /// See [Config::with_scan_workers]
+    scan_workers: usize,
+
+    /// See [Config::with_snapshot_file]
+    snapshot_file: Option<Arc<Path>>,
+
+    /// See [Config::with_snapshot_interval]
+    snapshot_interval: Option<Duration>,
+
+    /// See [Config::with_scan_complete_events]
+    scan_complete_events: bool,
+
+    /// See [Config::with_symlink_policy]
+    symlink_policy: SymlinkPolicy,
+
+    /// See [Config::with_gitignore]
+    #[cfg(feature = "gitignore")]
+    gitignore: bool,
+
+    /// See [Config::with_replay_file]
+    #[cfg(feature = "replay")]
+    replay_file: Option<Arc<Path>>,
+
+    /// See [Config::with_replay_pace]
+    #[cfg(feature = "replay")]
+    replay_pace: ReplayPace,
 }
 
 impl Config {
@@ -61,6 +210,33 @@ impl Config {
         self.poll_interval
     }
 
+    /// For [crate::PollWatcher]
+    ///
+    /// How the poll interval spaces out scans. With [PollSchedule::Adaptive], changes are found
+    /// quickly while a tree is busy, and an idle tree costs little.
+    ///
+    /// ```rust
+    /// # use std::time::Duration;
+    /// # use notify::{Config, PollSchedule};
+    /// let config = Config::default()
+    ///     .with_poll_interval(Duration::from_millis(500))
+    ///     .with_poll_schedule(PollSchedule::Adaptive {
+    ///         max_interval: Duration::from_secs(60),
+    ///     });
+    /// ```
+    ///
+    /// This can't be changed during runtime. Scans wait the poll interval after each other by
+    /// default.
+    pub fn with_poll_schedule(mut self, poll_schedule: PollSchedule) -> Self {
+        self.poll_schedule = poll_schedule;
+        self
+    }
+
+    /// Returns current setting
+    pub fn poll_schedule(&self) -> PollSchedule {
+        self.poll_schedule
+    }
+
     /// For [crate::PollWatcher]
     /// 
     /// Optional feature that will evaluate the contents of changed files to determine if
@@ -80,13 +256,488 @@ impl Config {
     pub fn compare_contents(&self) -> bool {
         self.compare_contents
     }
+
+    /// For [crate::PollWatcher]
+    ///
+    /// How file contents are hashed when [comparing contents](Config::with_compare_contents).
+    /// Hashing only parts of files, or leaving large files out, keeps scans of big trees cheap.
+    ///
+    /// ```rust
+    /// # use notify::{Config, HashStrategy};
+    /// let config = Config::default()
+    ///     .with_compare_contents(true)
+    ///     .with_hash_strategy(HashStrategy::head_tail(64 * 1024).size_limit(1 << 30));
+    /// ```
+    ///
+    /// Hashes saved in a [snapshot file](Config::with_snapshot_file) by another strategy report
+    /// all files as modified once.
+    ///
+    /// This can't be changed during runtime. Whole files are hashed by default.
+    pub fn with_hash_strategy(mut self, hash_strategy: HashStrategy) -> Self {
+        self.hash_strategy = Some(hash_strategy);
+        self
+    }
+
+    /// Returns current setting
+    pub fn hash_strategy(&self) -> Option<&HashStrategy> {
+        self.hash_strategy.as_ref()
+    }
+
+    /// For [crate::PollWatcher], [crate::INotifyWatcher] and `FanotifyWatcher`
+    ///
+    /// Allows watching paths that don't exist yet. Instead of failing, `watch` keeps the path
+    /// pending and activates the watch once the path is created. If a watched path is removed,
+    /// its watch goes back to pending until the path shows up again.
+    ///
+    /// While pending, the nearest existing ancestor of the path is watched for it to appear.
+    /// Events are only reported for the watched path itself, not for the ancestor.
+    ///
+    /// This can't be changed during runtime. Off by default.
+    pub fn with_pending_watches(mut self, pending_watches: bool) -> Self {
+        self.pending_watches = pending_watches;
+        self
+    }
+
+    /// Returns current setting
+    pub fn pending_watches(&self) -> bool {
+        self.pending_watches
+    }
+
+    /// For [crate::PollWatcher]
+    ///
+    /// Paths matching these rules are skipped while scanning. Ignored directories are not
+    /// descended into, so nothing below them is read or hashed. The watched root itself is never
+    /// ignored.
+    ///
+    /// ```rust
+    /// # use notify::{Config, IgnoreRules};
+    /// let config = Config::default()
+    ///     .with_ignore(IgnoreRules::patterns(["target", "node_modules", ".git", "*.tmp"]));
+    /// ```
+    ///
+    /// This can't be changed during runtime. Nothing is ignored by default.
+    pub fn with_ignore(mut self, ignore: IgnoreRules) -> Self {
+        self.ignore = Some(ignore);
+        self
+    }
+
+    /// Returns current setting
+    pub fn ignore(&self) -> Option<&IgnoreRules> {
+        self.ignore.as_ref()
+    }
+
+    /// Classes of events to subscribe to, see [EventKindMask] for the watchers using them
+    ///
+    /// This can't be changed during runtime. Defaults to [EventKindMask::default].
+    pub fn with_event_kinds(mut self, event_kinds: EventKindMask) -> Self {
+        self.event_kinds = event_kinds;
+        self
+    }
+
+    /// Returns current setting
+    pub fn event_kinds(&self) -> EventKindMask {
+        self.event_kinds
+    }
+
+    /// For [crate::INotifyWatcher]
+    ///
+    /// How long to wait for the second half of a rename. Both halves that arrive within the
+    /// window are reported as a single [RenameMode::Both](crate::event::RenameMode::Both) event
+    /// with the old and the new path. Otherwise the first half is reported on its own, as the
+    /// path was moved out of the watched directories.
+    ///
+    /// This can't be changed during runtime. The default window is 10 milliseconds.
+    pub fn with_rename_window(mut self, window: Duration) -> Self {
+        self.rename_window = window;
+        self
+    }
+
+    /// Returns current setting
+    pub fn rename_window(&self) -> Duration {
+        self.rename_window
+    }
+
+    /// For [crate::INotifyWatcher]
+    ///
+    /// When the kernel queue overflows, events are lost and by default an event with the
+    /// [Rescan](crate::event::Flag::Rescan) flag is all that is reported. With recovery enabled,
+    /// the watcher keeps a snapshot of the watched trees instead. After an overflow it rescans
+    /// them, emits create, remove and modify events for what changed and places the directory
+    /// watches that were missed, so no rescan is left to do.
+    ///
+    /// The snapshot costs memory for every watched path and a `stat` for every event.
+    ///
+    /// This can't be changed during runtime. Off by default.
+    pub fn with_overflow_recovery(mut self, overflow_recovery: bool) -> Self {
+        self.overflow_recovery = overflow_recovery;
+        self
+    }
+
+    /// Returns current setting
+    pub fn overflow_recovery(&self) -> bool {
+        self.overflow_recovery
+    }
+
+    /// For [crate::PollWatcher]
+    ///
+    /// Number of threads scanning the watched trees, each taking one directory at a time.
+    /// Helps when reading directories is slow, like on network shares with many files.
+    /// The threads besides the one scanning are started with the watcher and live as long as it.
+    /// A value of 0 is treated as 1.
+    ///
+    /// This can't be changed during runtime. Defaults to 1, scanning on the poll thread only.
+    pub fn with_scan_workers(mut self, scan_workers: usize) -> Self {
+        self.scan_workers = scan_workers.max(1);
+        self
+    }
+
+    /// Returns current setting
+    pub fn scan_workers(&self) -> usize {
+        self.scan_workers
+    }
+
+    /// For [crate::PollWatcher]
+    ///
+    /// Saves the modification time, size and content hash of every scanned path to `file` when
+    /// the watcher is dropped, and loads it when the watcher is created. When a path is watched
+    /// again with the same [RecursiveMode], its first scan reports what was created, modified and
+    /// removed since it was saved, instead of taking the files as they are as the baseline.
+    ///
+    /// Content hashes are only compared if [Config::with_compare_contents] was on in both runs.
+    /// An unreadable file is reported as an error event and replaced on the next save.
+    ///
+    /// This can't be changed during runtime. Off by default.
+    pub fn with_snapshot_file<P: Into<PathBuf>>(mut self, file: P) -> Self {
+        self.snapshot_file = Some(file.into().into());
+        self
+    }
+
+    /// Returns current setting
+    pub fn snapshot_file(&self) -> Option<&Path> {
+        self.snapshot_file.as_deref()
+    }
+
+    /// For [crate::PollWatcher]
+    ///
+    /// Also saves the [snapshot file](Config::with_snapshot_file) after a scan, at most once per
+    /// `interval`, so a crash loses less.
+    ///
+    /// This can't be changed during runtime. By default the snapshot is only saved on drop.
+    pub fn with_snapshot_interval(mut self, interval: Duration) -> Self {
+        self.snapshot_interval = Some(interval);
+        self
+    }
+
+    /// Returns current setting
+    pub fn snapshot_interval(&self) -> Option<Duration> {
+        self.snapshot_interval
+    }
+
+    /// For [crate::PollWatcher]
+    ///
+    /// Emits an event with the [ScanComplete](crate::event::Flag::ScanComplete) flag after each
+    /// scan of the watched paths, so the changes found by one scan can be handled as a batch.
+    ///
+    /// This can't be changed during runtime. Off by default.
+    pub fn with_scan_complete_events(mut self, scan_complete_events: bool) -> Self {
+        self.scan_complete_events = scan_complete_events;
+        self
+    }
+
+    /// Returns current setting
+    pub fn scan_complete_events(&self) -> bool {
+        self.scan_complete_events
+    }
+
+    /// For [crate::PollWatcher] and [crate::INotifyWatcher]
+    ///
+    /// Which symbolic links recursive watches descend into. Links that are not followed, or
+    /// don't resolve, are watched like files. A directory containing itself through a link is
+    /// only read once, whatever the policy. Events are reported under the paths the links
+    /// were found at, the watched path itself is always followed.
+    ///
+    /// This can't be changed during runtime. Links are followed by default.
+    pub fn with_symlink_policy(mut self, symlink_policy: SymlinkPolicy) -> Self {
+        self.symlink_policy = symlink_policy;
+        self
+    }
+
+    /// Returns current setting
+    pub fn symlink_policy(&self) -> SymlinkPolicy {
+        self.symlink_policy
+    }
+
+    /// For all backends, requires the `gitignore` feature
+    ///
+    /// Skips paths ignored by `.gitignore` and `.ignore` files, read hierarchically under the
+    /// watched paths up to the repository root, and by `.git/info/exclude`. Outside of a
+    /// repository, only the files in the watched paths are read. The `.git` directory itself is
+    /// skipped as well. [crate::INotifyWatcher] places no watches inside ignored
+    /// directories and [crate::PollWatcher] doesn't scan them, other backends drop their events.
+    ///
+    /// Changed `.gitignore` and `.ignore` files are reloaded as their events come in.
+    /// `.git/info/exclude` is only read once, as nothing inside `.git` is watched.
+    ///
+    /// This can't be changed during runtime. Off by default.
+    #[cfg(feature = "gitignore")]
+    pub fn with_gitignore(mut self, gitignore: bool) -> Self {
+        self.gitignore = gitignore;
+        self
+    }
+
+    /// Returns current setting
+    #[cfg(feature = "gitignore")]
+    pub fn gitignore(&self) -> bool {
+        self.gitignore
+    }
+
+    /// For [crate::replay::ReplayWatcher], requires the `replay` feature
+    ///
+    /// Recording to play back, as written by a [crate::replay::RecordingHandler].
+    ///
+    /// This can't be changed during runtime. Creating a `ReplayWatcher` fails without it.
+    #[cfg(feature = "replay")]
+    pub fn with_replay_file<P: Into<PathBuf>>(mut self, file: P) -> Self {
+        self.replay_file = Some(file.into().into());
+        self
+    }
+
+    /// Returns current setting
+    #[cfg(feature = "replay")]
+    pub fn replay_file(&self) -> Option<&Path> {
+        self.replay_file.as_deref()
+    }
+
+    /// For [crate::replay::ReplayWatcher], requires the `replay` feature
+    ///
+    /// Whether recorded events are delivered as far apart as they were recorded, or right after
+    /// each other.
+    ///
+    /// This can't be changed during runtime. Defaults to [ReplayPace::RealTime].
+    #[cfg(feature = "replay")]
+    pub fn with_replay_pace(mut self, pace: ReplayPace) -> Self {
+        self.replay_pace = pace;
+        self
+    }
+
+    /// Returns current setting
+    #[cfg(feature = "replay")]
+    pub fn replay_pace(&self) -> ReplayPace {
+        self.replay_pace
+    }
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self { 
             poll_interval: Duration::from_secs(30),
-            compare_contents: false
+            poll_schedule: PollSchedule::default(),
+            compare_contents: false,
+            hash_strategy: None,
+            pending_watches: false,
+            ignore: None,
+            event_kinds: EventKindMask::default(),
+            rename_window: Duration::from_millis(10),
+            overflow_recovery: false,
+            scan_workers: 1,
+            snapshot_file: None,
+            snapshot_interval: None,
+            scan_complete_events: false,
+            symlink_policy: SymlinkPolicy::default(),
+            #[cfg(feature = "gitignore")]
+            gitignore: false,
+            #[cfg(feature = "replay")]
+            replay_file: None,
+            #[cfg(feature = "replay")]
+            replay_pace: ReplayPace::default(),
         }
     }
-}
\ No newline at end of file
+}
+
+bitflags::bitflags! {
+    /// Classes of events a watcher subscribes to, see [Config::with_event_kinds]
+    ///
+    /// [crate::INotifyWatcher] only subscribes to these classes. Dropping
+    /// [EventKindMask::MODIFY_DATA] silences the stream of writes when
+    /// [EventKindMask::ACCESS_CLOSE_WRITE] is enough to know a file changed, while the access
+    /// classes allow auditing reads.
+    ///
+    /// ```rust
+    /// # use notify::{Config, EventKindMask};
+    /// let config = Config::default()
+    ///     .with_event_kinds(EventKindMask::default() - EventKindMask::MODIFY_DATA);
+    /// ```
+    ///
+    /// Without [EventKindMask::MODIFY_NAME], paths moved into a watched directory are reported as
+    /// created, and paths moved out of it as removed. Recursive watches keep following new
+    /// directories whatever the classes are.
+    ///
+    /// Set for a single path with [WatchOptions::with_event_kinds], these classes override those
+    /// of the [Config]. Watchers other than [crate::INotifyWatcher] receive every event and drop
+    /// the ones of other classes before they reach the handler, and deliver all events to paths
+    /// without classes of their own. Events of paths below another watched path are filtered by
+    /// the nearest one.
+    pub struct EventKindMask: u32 {
+        /// Files and directories created
+        const CREATE = 0b0000_0001;
+        /// Files and directories removed
+        const REMOVE = 0b0000_0010;
+        /// Every write to a file
+        const MODIFY_DATA = 0b0000_0100;
+        /// Permissions, timestamps, ownership and extended attributes changed
+        const MODIFY_META = 0b0000_1000;
+        /// Files and directories renamed or moved
+        const MODIFY_NAME = 0b0001_0000;
+        /// Files read
+        const ACCESS_READ = 0b0010_0000;
+        /// Files and directories opened
+        const ACCESS_OPEN = 0b0100_0000;
+        /// Files opened for writing closed
+        const ACCESS_CLOSE_WRITE = 0b1000_0000;
+        /// Files and directories opened read-only closed
+        const ACCESS_CLOSE_READ = 0b1_0000_0000;
+    }
+}
+
+impl EventKindMask {
+    /// Returns whether events of `kind` belong to these classes
+    ///
+    /// Kinds no class describes always match.
+    pub fn matches(&self, kind: &EventKind) -> bool {
+        let class = match kind {
+            EventKind::Create(_) => Self::CREATE,
+            EventKind::Remove(_) => Self::REMOVE,
+            EventKind::Modify(ModifyKind::Data(_)) => Self::MODIFY_DATA,
+            EventKind::Modify(ModifyKind::Metadata(_)) => Self::MODIFY_META,
+            EventKind::Modify(ModifyKind::Name(_)) => Self::MODIFY_NAME,
+            EventKind::Access(AccessKind::Read) => Self::ACCESS_READ,
+            EventKind::Access(AccessKind::Open(_)) => Self::ACCESS_OPEN,
+            EventKind::Access(AccessKind::Close(AccessMode::Write)) => Self::ACCESS_CLOSE_WRITE,
+            EventKind::Access(AccessKind::Close(_)) => Self::ACCESS_CLOSE_READ,
+            _ => return true,
+        };
+        self.intersects(class)
+    }
+}
+
+impl Default for EventKindMask {
+    /// Everything but the access classes, except [EventKindMask::ACCESS_CLOSE_WRITE]
+    fn default() -> Self {
+        Self::CREATE
+            | Self::REMOVE
+            | Self::MODIFY_DATA
+            | Self::MODIFY_META
+            | Self::MODIFY_NAME
+            | Self::ACCESS_CLOSE_WRITE
+    }
+}
+
+/// Rules deciding which paths are skipped, see [Config::with_ignore]
+///
+/// Two rules are only equal if they are clones of each other.
+#[derive(Clone)]
+pub struct IgnoreRules(Arc<dyn Fn(&Path) -> bool + Send + Sync>);
+
+impl IgnoreRules {
+    /// Ignore what either `a` or `b` ignores
+    pub(crate) fn either(a: Option<IgnoreRules>, b: Option<IgnoreRules>) -> Option<IgnoreRules> {
+        match (a, b) {
+            (Some(a), Some(b)) => Some(Self::new(move |path| {
+                a.is_ignored(path) || b.is_ignored(path)
+            })),
+            (a, b) => a.or(b),
+        }
+    }
+
+    /// Ignore every path for which `predicate` returns `true`
+    pub fn new<F>(predicate: F) -> Self
+    where
+        F: Fn(&Path) -> bool + Send + Sync + 'static,
+    {
+        IgnoreRules(Arc::new(predicate))
+    }
+
+    /// Ignore every path whose file name matches one of the `patterns`
+    ///
+    /// Patterns match whole file names, `*` matches any number of characters and `?` exactly one.
+    pub fn patterns<I, S>(patterns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let patterns: Vec<String> = patterns.into_iter().map(Into::into).collect();
+        Self::new(move |path| {
+            let name = match path.file_name() {
+                Some(name) => name.to_string_lossy(),
+                None => return false,
+            };
+            patterns.iter().any(|pattern| glob_match(pattern, &name))
+        })
+    }
+
+    /// Returns whether `path` is ignored
+    pub fn is_ignored(&self, path: &Path) -> bool {
+        (self.0)(path)
+    }
+}
+
+impl PartialEq for IgnoreRules {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for IgnoreRules {}
+
+impl Hash for IgnoreRules {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (Arc::as_ptr(&self.0) as *const () as usize).hash(state);
+    }
+}
+
+impl fmt::Debug for IgnoreRules {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("IgnoreRules").finish()
+    }
+}
+
+/// Match `name` against a pattern with `*` and `?` wildcards
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // position of the last `*` and of the name when it was reached, to backtrack to
+    let mut star = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    p = star_p + 1;
+                    n = star_n + 1;
+                    star = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[test]
+fn ignore_patterns_match_file_names() {
+    let rules = IgnoreRules::patterns(["target", "*.tmp", "?.log"]);
+    assert!(rules.is_ignored(Path::new("/repo/target")));
+    assert!(rules.is_ignored(Path::new("/repo/src/lib.rs.tmp")));
+    assert!(rules.is_ignored(Path::new("/repo/a.log")));
+    assert!(!rules.is_ignored(Path::new("/repo/target2")));
+    assert!(!rules.is_ignored(Path::new("/repo/ab.log")));
+    assert!(!rules.is_ignored(Path::new("/target/lib.rs")));
+}
@@ -28,7 +28,7 @@ pub enum ErrorKind {
     WatchNotFound,
 
     /// An invalid value was passed as runtime configuration.
-    InvalidConfig(Config),
+    InvalidConfig(Box<Config>),
 
     /// Can't watch (more) files, limit on the total number of inotify watches reached
     MaxFilesWatch,
@@ -93,7 +93,7 @@ impl Error {
 
     /// Creates a new "invalid config" error from the given `Config`.
     pub fn invalid_config(config: &Config) -> Self {
-        Self::new(ErrorKind::InvalidConfig(config.clone()))
+        Self::new(ErrorKind::InvalidConfig(Box::new(config.clone())))
     }
 }
 
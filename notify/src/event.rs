@@ -473,7 +473,7 @@ impl EventAttributes {
 
     fn inner_mut(&mut self) -> &mut EventAttributesInner {
         self.inner
-            .get_or_insert_with(|| Box::new(Default::default()))
+            .get_or_insert_with(Box::default)
     }
 }
 
@@ -505,6 +505,12 @@ pub enum Flag {
     /// that keeps an in-memory representation of the filesystem will need to care, and will need
     /// to refresh that representation directly from the filesystem.
     Rescan,
+
+    /// Scan complete notices are emitted by the [`PollWatcher`](crate::PollWatcher) after each
+    /// scan of the watched paths, if enabled with
+    /// [`Config::with_scan_complete_events`](crate::Config::with_scan_complete_events). They carry
+    /// no paths and follow all events found by the scan.
+    ScanComplete,
 }
 
 impl Event {
@@ -0,0 +1,228 @@
+//! Watcher driven by test code
+//!
+//! A [`FakeWatcher`] doesn't look at the filesystem. Its [`FakeController`] delivers events and
+//! errors to the event handler, tells which paths are watched, and makes watching fail, so code
+//! reacting to events can be tested without touching the disk.
+//!
+//! ```rust
+//! use notify::event::{CreateKind, EventKind};
+//! use notify::{Config, Event, FakeWatcher, RecursiveMode, Result, Watcher};
+//! use std::path::Path;
+//! use std::sync::mpsc;
+//!
+//! let (tx, rx) = mpsc::channel::<Result<Event>>();
+//! let mut watcher = FakeWatcher::new(tx, Config::default()).unwrap();
+//! let controller = watcher.controller();
+//!
+//! watcher.watch(Path::new("/srv"), RecursiveMode::Recursive).unwrap();
+//! assert_eq!(
+//!     controller.recursive_mode(Path::new("/srv")),
+//!     Some(RecursiveMode::Recursive)
+//! );
+//!
+//! let event = Event::new(EventKind::Create(CreateKind::File)).add_path("/srv/a".into());
+//! controller.send_event(event);
+//! assert_eq!(rx.recv().unwrap().unwrap().paths, [Path::new("/srv/a")]);
+//! ```
+
+use crate::filter::WatchFilters;
+use crate::{
+    Config, Error, Event, EventHandler, RecursiveMode, Result, WatchInfo, WatchOptions, Watcher,
+};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Watcher whose events come from its [`FakeController`], see the [module docs](self)
+///
+/// Like a real watcher, it filters the events by the kinds asked for with
+/// [`Watcher::watch_with`], and fails to unwatch paths that aren't watched.
+#[derive(Debug)]
+pub struct FakeWatcher {
+    controller: FakeController,
+}
+
+/// Handle of a [`FakeWatcher`] for the test code
+///
+/// It can be cloned and sent to other threads, and keeps working after the watcher is dropped,
+/// the events it sends then going nowhere.
+#[derive(Clone)]
+pub struct FakeController {
+    /// Not locked with `state`, so the handler can use the controller
+    handler: Arc<Mutex<Option<Box<dyn EventHandler>>>>,
+    /// The watched paths, filtering the events of the handler
+    filters: WatchFilters,
+    state: Arc<Mutex<FakeState>>,
+}
+
+#[derive(Debug, Default)]
+struct FakeState {
+    /// Errors to return from the next calls to `watch`
+    watch_errors: VecDeque<Error>,
+    /// The config of the last call to `configure`
+    config: Option<Config>,
+}
+
+impl FakeWatcher {
+    /// Returns the handle to drive this watcher with
+    pub fn controller(&self) -> FakeController {
+        self.controller.clone()
+    }
+}
+
+impl FakeController {
+    /// Deliver `event` to the event handler, unless no watch asks for its kind
+    pub fn send_event(&self, event: Event) {
+        self.send(Ok(event));
+    }
+
+    /// Deliver `error` to the event handler
+    pub fn send_error(&self, error: Error) {
+        self.send(Err(error));
+    }
+
+    /// Deliver an event or an error to the event handler, see [`FakeController::send_event`]
+    pub fn send(&self, event: Result<Event>) {
+        let mut handler = self.handler.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(handler) = handler.as_mut() {
+            handler.handle_event(event);
+        }
+    }
+
+    /// Returns the mode `path` is watched in, if it is watched
+    pub fn recursive_mode(&self, path: &Path) -> Option<RecursiveMode> {
+        self.filters
+            .lock()
+            .get(path)
+            .map(WatchOptions::recursive_mode)
+    }
+
+    /// Returns the options of the watched paths
+    pub fn watches(&self) -> HashMap<PathBuf, WatchOptions> {
+        self.filters.lock().clone()
+    }
+
+    /// Make the next call to [`Watcher::watch`] or [`Watcher::watch_with`] fail with `error`.
+    ///
+    /// Calling this repeatedly makes as many calls fail, in order.
+    ///
+    /// ```rust
+    /// # use notify::{Config, Error, ErrorKind, FakeWatcher, RecursiveMode, Watcher};
+    /// # use std::path::Path;
+    /// let mut watcher = FakeWatcher::new(|_| {}, Config::default()).unwrap();
+    /// watcher.controller().fail_next_watch(Error::new(ErrorKind::MaxFilesWatch));
+    ///
+    /// let res = watcher.watch(Path::new("/srv"), RecursiveMode::Recursive);
+    /// assert!(matches!(res.unwrap_err().kind, ErrorKind::MaxFilesWatch));
+    /// ```
+    pub fn fail_next_watch(&self, error: Error) {
+        self.state().watch_errors.push_back(error);
+    }
+
+    /// Returns the config of the last call to [`Watcher::configure`]
+    pub fn config(&self) -> Option<Config> {
+        self.state().config.clone()
+    }
+
+    fn state(&self) -> MutexGuard<'_, FakeState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl fmt::Debug for FakeController {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FakeController")
+            .field("watches", &self.filters)
+            .field("state", &*self.state())
+            .finish()
+    }
+}
+
+impl Watcher for FakeWatcher {
+    fn new<F: EventHandler>(event_handler: F, _config: Config) -> Result<Self> {
+        let filters = WatchFilters::default();
+        let event_handler = filters.wrap_handler(event_handler);
+        let controller = FakeController {
+            handler: Arc::new(Mutex::new(Some(event_handler))),
+            filters,
+            state: Arc::default(),
+        };
+        Ok(FakeWatcher { controller })
+    }
+
+    fn watch(&mut self, path: &Path, recursive_mode: RecursiveMode) -> Result<()> {
+        self.watch_with(path, WatchOptions::new(recursive_mode))
+    }
+
+    fn watch_with(&mut self, path: &Path, options: WatchOptions) -> Result<()> {
+        if let Some(error) = self.controller.state().watch_errors.pop_front() {
+            return Err(error);
+        }
+        self.controller
+            .filters
+            .add(path.to_path_buf(), options, || Ok(()))
+    }
+
+    fn unwatch(&mut self, path: &Path) -> Result<()> {
+        self.controller.filters.unwatch(path)
+    }
+
+    fn configure(&mut self, config: Config) -> Result<bool> {
+        self.controller.state().config = Some(config);
+        Ok(true)
+    }
+
+    fn watches(&self) -> Result<Vec<WatchInfo>> {
+        // no kernel watches
+        Ok(self.controller.filters.watch_infos(|_, _| 0))
+    }
+
+    fn kind() -> crate::WatcherKind {
+        crate::WatcherKind::FakeWatcher
+    }
+}
+
+impl Drop for FakeWatcher {
+    fn drop(&mut self) {
+        // like the other watchers, drop the handler with the watcher
+        *self
+            .controller
+            .handler
+            .lock()
+            .unwrap_or_else(|e| e.into_inner()) = None;
+    }
+}
+
+#[test]
+fn fake_watcher_filters_injected_events() {
+    use crate::event::{CreateKind, DataChange, EventKind, ModifyKind};
+    use crate::EventKindMask;
+    use std::sync::mpsc;
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = FakeWatcher::new(tx, Config::default()).unwrap();
+    let controller = watcher.controller();
+    let options =
+        WatchOptions::new(RecursiveMode::Recursive).with_event_kinds(EventKindMask::CREATE);
+    watcher.watch_with(Path::new("/srv"), options).unwrap();
+
+    let modify =
+        Event::new(EventKind::Modify(ModifyKind::Data(DataChange::Any))).add_path("/srv/a".into());
+    let create = Event::new(EventKind::Create(CreateKind::File)).add_path("/srv/a".into());
+    controller.send_event(modify);
+    controller.send_event(create);
+    controller.send_error(Error::generic("injected"));
+    let received: Vec<_> = rx.try_iter().collect();
+    assert_eq!(received.len(), 2);
+    assert!(received[0].as_ref().unwrap().kind.is_create());
+    assert!(received[1].is_err());
+
+    watcher.unwatch(Path::new("/srv")).unwrap();
+    assert_eq!(controller.recursive_mode(Path::new("/srv")), None);
+    assert!(watcher.unwatch(Path::new("/srv")).is_err());
+
+    drop(watcher);
+    controller.send_event(Event::new(EventKind::Any));
+    assert!(rx.recv().is_err());
+}
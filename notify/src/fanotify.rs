@@ -0,0 +1,789 @@
+//! Watcher implementation for the fanotify Linux API
+//!
+//! In contrast to inotify, fanotify can watch a whole filesystem (or mount) with a single mark, so
+//! recursive watches of large trees do not run into the `max_user_watches` limit. Events are
+//! reported for the entire filesystem and filtered down to the watched paths in userspace.
+//!
+//! Each event carries the process ID of the process that caused it, see
+//! [`Event::process_id`](crate::event::EventAttributes::process_id).
+//!
+//! # Requirements
+//!
+//! - Linux 5.9 or newer, for `FAN_REPORT_DFID_NAME`
+//! - the `CAP_SYS_ADMIN` capability, required by `fanotify_init` for `FAN_MARK_FILESYSTEM`
+//! - the `CAP_DAC_READ_SEARCH` capability, to resolve reported file handles to paths
+//!
+//! Without `CAP_SYS_ADMIN` the watcher fails to be created with an error explaining as much.
+//!
+//! Filesystems that can't be marked as a whole (e.g. btrfs subvolumes) fall back to a mount mark,
+//! which only reports data modifications; creations, removals, renames and metadata changes are
+//! not available in that case.
+
+use super::event::*;
+use super::{
+    Config, Error, EventHandler, RecursiveMode, Result, WatchInfo, WatchOptions, WatchStatus,
+    Watcher,
+};
+use crate::filter::{filtered_handler, WatchFilters};
+use crate::{unbounded, Receiver, Sender};
+use std::collections::HashMap;
+use std::env;
+use std::ffi::{CString, OsStr};
+use std::fs::{self, File};
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+
+const FANOTIFY: mio::Token = mio::Token(0);
+const MESSAGE: mio::Token = mio::Token(1);
+
+/// Events requested for filesystem and directory marks
+const MASK: u64 = libc::FAN_CREATE
+    | libc::FAN_DELETE
+    | libc::FAN_DELETE_SELF
+    | libc::FAN_MOVED_FROM
+    | libc::FAN_MOVED_TO
+    | libc::FAN_MOVE_SELF
+    | libc::FAN_MODIFY
+    | libc::FAN_ATTRIB
+    | libc::FAN_CLOSE_WRITE
+    | libc::FAN_ONDIR;
+
+/// Events requested for mount marks, which don't support directory entry events
+const MOUNT_MASK: u64 = libc::FAN_MODIFY | libc::FAN_CLOSE_WRITE;
+
+const EVENT_METADATA_LEN: usize = std::mem::size_of::<libc::fanotify_event_metadata>();
+
+/// Kind of a fanotify mark
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MarkKind {
+    Filesystem,
+    Mount,
+    Inode,
+}
+
+impl MarkKind {
+    fn flags(self) -> u32 {
+        match self {
+            MarkKind::Filesystem => libc::FAN_MARK_FILESYSTEM,
+            MarkKind::Mount => libc::FAN_MARK_MOUNT,
+            MarkKind::Inode => 0,
+        }
+    }
+
+    fn mask(self) -> u64 {
+        match self {
+            MarkKind::Mount => MOUNT_MASK,
+            MarkKind::Filesystem => MASK,
+            MarkKind::Inode => MASK | libc::FAN_EVENT_ON_CHILD,
+        }
+    }
+}
+
+/// A watched path
+struct WatchRoot {
+    is_recursive: bool,
+    /// Device the root lives on, used as key into `marks`
+    dev: u64,
+    /// The mark this root was added with
+    kind: MarkKind,
+}
+
+/// A placed fanotify mark, shared by all roots on the same device
+struct Mark {
+    kind: MarkKind,
+    /// Path the mark was placed with
+    path: PathBuf,
+    /// Number of roots using this mark
+    refs: usize,
+    /// Handle on the filesystem, used to resolve file handles of its events
+    mount_fd: File,
+    /// ID of the filesystem, as reported in the events of its files
+    fsid: Option<[i32; 2]>,
+}
+
+// The EventLoop will set up a mio::Poll and use it to wait for the following:
+//
+// -  messages telling it what to do
+//
+// -  events telling it that something has happened on one of the watched filesystems.
+struct EventLoop {
+    running: bool,
+    poll: mio::Poll,
+    event_loop_waker: Arc<mio::Waker>,
+    event_loop_tx: Sender<EventLoopMsg>,
+    event_loop_rx: Receiver<EventLoopMsg>,
+    fanotify: Option<File>,
+    event_handler: Box<dyn EventHandler>,
+    roots: HashMap<PathBuf, WatchRoot>,
+    /// Marks by device; inode marks are keyed separately by their path
+    marks: HashMap<(u64, Option<PathBuf>), Mark>,
+    pending_watches: bool,
+    /// Read buffer for events, kept across reads
+    buffer: Vec<u8>,
+}
+
+/// Watcher implementation based on fanotify
+///
+/// See the [module documentation](self) for its requirements.
+#[derive(Debug)]
+pub struct FanotifyWatcher {
+    channel: Sender<EventLoopMsg>,
+    waker: Arc<mio::Waker>,
+    filters: WatchFilters,
+}
+
+enum EventLoopMsg {
+    AddWatch(PathBuf, RecursiveMode, Sender<Result<()>>),
+    RemoveWatch(PathBuf, Sender<Result<()>>),
+    Watches(Sender<Vec<WatchInfo>>),
+    Shutdown,
+}
+
+impl EventLoop {
+    pub fn new(
+        fanotify: File,
+        event_handler: Box<dyn EventHandler>,
+        config: Config,
+    ) -> Result<Self> {
+        let (event_loop_tx, event_loop_rx) = unbounded::<EventLoopMsg>();
+        let poll = mio::Poll::new()?;
+
+        let event_loop_waker = Arc::new(mio::Waker::new(poll.registry(), MESSAGE)?);
+
+        let fanotify_fd = fanotify.as_raw_fd();
+        let mut evented_fanotify = mio::unix::SourceFd(&fanotify_fd);
+        poll.registry()
+            .register(&mut evented_fanotify, FANOTIFY, mio::Interest::READABLE)?;
+
+        let event_loop = EventLoop {
+            running: true,
+            poll,
+            event_loop_waker,
+            event_loop_tx,
+            event_loop_rx,
+            fanotify: Some(fanotify),
+            event_handler,
+            roots: HashMap::new(),
+            marks: HashMap::new(),
+            pending_watches: config.pending_watches(),
+            buffer: vec![0u8; 16 * 1024],
+        };
+        Ok(event_loop)
+    }
+
+    // Run the event loop.
+    pub fn run(self) {
+        let _ = thread::Builder::new()
+            .name("notify-rs fanotify loop".to_string())
+            .spawn(|| self.event_loop_thread());
+    }
+
+    fn event_loop_thread(mut self) {
+        let mut events = mio::Events::with_capacity(16);
+        loop {
+            // Wait for something to happen.
+            match self.poll.poll(&mut events, None) {
+                Err(ref e) if matches!(e.kind(), io::ErrorKind::Interrupted) => {
+                    // System call was interrupted, we will retry
+                }
+                Err(e) => panic!("poll failed: {}", e),
+                Ok(()) => {}
+            }
+
+            // Process whatever happened.
+            for event in &events {
+                match event.token() {
+                    MESSAGE => self.handle_messages(),
+                    FANOTIFY => self.handle_fanotify(),
+                    _ => unreachable!(),
+                }
+            }
+
+            // Stop, if we're done.
+            if !self.running {
+                break;
+            }
+        }
+    }
+
+    fn handle_messages(&mut self) {
+        while let Ok(msg) = self.event_loop_rx.try_recv() {
+            match msg {
+                EventLoopMsg::AddWatch(path, recursive_mode, tx) => {
+                    let _ = tx.send(self.add_watch(path, recursive_mode.is_recursive()));
+                }
+                EventLoopMsg::RemoveWatch(path, tx) => {
+                    let _ = tx.send(self.remove_watch(path));
+                }
+                EventLoopMsg::Watches(tx) => {
+                    let _ = tx.send(self.watch_infos());
+                }
+                EventLoopMsg::Shutdown => {
+                    // closing the fanotify descriptor removes all marks
+                    self.fanotify.take();
+                    self.marks.clear();
+                    self.roots.clear();
+                    self.running = false;
+                    break;
+                }
+            }
+        }
+    }
+
+    fn handle_fanotify(&mut self) {
+        let fd = match self.fanotify {
+            Some(ref fanotify) => fanotify.as_raw_fd(),
+            None => return,
+        };
+
+        // taken while its events borrow it
+        let mut buffer = std::mem::take(&mut self.buffer);
+        loop {
+            let len = unsafe { libc::read(fd, buffer.as_mut_ptr() as *mut _, buffer.len()) };
+            if len < 0 {
+                let err = io::Error::last_os_error();
+                match err.kind() {
+                    io::ErrorKind::WouldBlock => break,
+                    io::ErrorKind::Interrupted => continue,
+                    _ => {
+                        self.event_handler.handle_event(Err(Error::io(err)));
+                        break;
+                    }
+                }
+            }
+            if len == 0 {
+                break;
+            }
+
+            let len = len as usize;
+            let mut offset = 0;
+            while offset + EVENT_METADATA_LEN <= len {
+                let metadata: libc::fanotify_event_metadata =
+                    unsafe { std::ptr::read_unaligned(buffer[offset..].as_ptr() as *const _) };
+                let event_len = metadata.event_len as usize;
+                if event_len < EVENT_METADATA_LEN || offset + event_len > len {
+                    break;
+                }
+                if metadata.fd >= 0 {
+                    unsafe { libc::close(metadata.fd) };
+                }
+
+                let info = &buffer[offset + metadata.metadata_len as usize..offset + event_len];
+                self.handle_fanotify_event(&metadata, info);
+                offset += event_len;
+            }
+
+            // a busy filesystem may never run dry, don't starve the message channel
+            self.handle_messages();
+            if !self.running {
+                break;
+            }
+        }
+        self.buffer = buffer;
+    }
+
+    fn handle_fanotify_event(&mut self, metadata: &libc::fanotify_event_metadata, info: &[u8]) {
+        if metadata.mask & libc::FAN_Q_OVERFLOW != 0 {
+            let ev = Ok(Event::new(EventKind::Other).set_flag(Flag::Rescan));
+            self.event_handler.handle_event(ev);
+            return;
+        }
+
+        let path = match self.info_path(info) {
+            Some(path) if self.is_watched(&path) => path,
+            _ => return,
+        };
+
+        let mask = metadata.mask;
+        let is_dir = mask & libc::FAN_ONDIR != 0;
+        let mut kinds = Vec::new();
+        if mask & libc::FAN_CREATE != 0 {
+            kinds.push(EventKind::Create(if is_dir {
+                CreateKind::Folder
+            } else {
+                CreateKind::File
+            }));
+        }
+        if mask & libc::FAN_MOVED_FROM != 0 || mask & libc::FAN_MOVE_SELF != 0 {
+            kinds.push(EventKind::Modify(ModifyKind::Name(RenameMode::From)));
+        }
+        if mask & libc::FAN_MOVED_TO != 0 {
+            kinds.push(EventKind::Modify(ModifyKind::Name(RenameMode::To)));
+        }
+        if mask & libc::FAN_DELETE != 0 || mask & libc::FAN_DELETE_SELF != 0 {
+            kinds.push(EventKind::Remove(if is_dir {
+                RemoveKind::Folder
+            } else {
+                RemoveKind::File
+            }));
+        }
+        if mask & libc::FAN_MODIFY != 0 {
+            kinds.push(EventKind::Modify(ModifyKind::Data(DataChange::Any)));
+        }
+        if mask & libc::FAN_CLOSE_WRITE != 0 {
+            kinds.push(EventKind::Access(AccessKind::Close(AccessMode::Write)));
+        }
+        if mask & libc::FAN_ATTRIB != 0 {
+            kinds.push(EventKind::Modify(ModifyKind::Metadata(MetadataKind::Any)));
+        }
+
+        for kind in kinds {
+            let mut ev = Event::new(kind).add_path(path.clone());
+            if metadata.pid > 0 {
+                ev = ev.set_process_id(metadata.pid as u32);
+            }
+            self.event_handler.handle_event(Ok(ev));
+        }
+    }
+
+    /// Resolve the path of an event from its information records
+    fn info_path(&self, mut info: &[u8]) -> Option<PathBuf> {
+        // struct fanotify_event_info_header { u8 info_type; u8 pad; u16 len; }
+        // followed by __kernel_fsid_t fsid and struct file_handle
+        while info.len() >= 4 {
+            let info_type = info[0];
+            let len = u16::from_ne_bytes([info[2], info[3]]) as usize;
+            if len < 4 || len > info.len() {
+                return None;
+            }
+            let record = &info[4..len];
+            info = &info[len..];
+
+            let has_name = match info_type {
+                libc::FAN_EVENT_INFO_TYPE_DFID_NAME => true,
+                libc::FAN_EVENT_INFO_TYPE_DFID | libc::FAN_EVENT_INFO_TYPE_FID => false,
+                _ => continue,
+            };
+            if record.len() < 16 {
+                return None;
+            }
+            let fsid = [
+                i32::from_ne_bytes([record[0], record[1], record[2], record[3]]),
+                i32::from_ne_bytes([record[4], record[5], record[6], record[7]]),
+            ];
+            let handle = &record[8..];
+            let handle_bytes =
+                u32::from_ne_bytes([handle[0], handle[1], handle[2], handle[3]]) as usize;
+            if handle.len() < 8 + handle_bytes {
+                return None;
+            }
+            let (handle, name) = handle.split_at(8 + handle_bytes);
+
+            let dir = self.resolve_handle(fsid, handle)?;
+            if !has_name {
+                return Some(dir);
+            }
+            let name = name.split(|b| *b == 0).next().unwrap_or_default();
+            return Some(if name.is_empty() || name == b"." {
+                dir
+            } else {
+                dir.join(OsStr::from_bytes(name))
+            });
+        }
+        None
+    }
+
+    /// Open a file handle relative to the marked filesystem it belongs to and read back its path
+    fn resolve_handle(&self, fsid: [i32; 2], handle: &[u8]) -> Option<PathBuf> {
+        // `struct file_handle` has to be 4 byte aligned
+        let mut aligned = vec![0u32; (handle.len() + 3) / 4];
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                handle.as_ptr(),
+                aligned.as_mut_ptr() as *mut u8,
+                handle.len(),
+            );
+        }
+
+        for mark in self.marks.values() {
+            if mark.fsid != Some(fsid) {
+                continue;
+            }
+            let fd = unsafe {
+                libc::syscall(
+                    libc::SYS_open_by_handle_at,
+                    mark.mount_fd.as_raw_fd(),
+                    aligned.as_mut_ptr(),
+                    libc::O_PATH | libc::O_CLOEXEC,
+                )
+            };
+            if fd < 0 {
+                continue;
+            }
+            let file = unsafe { File::from_raw_fd(fd as RawFd) };
+            return fs::read_link(format!("/proc/self/fd/{}", file.as_raw_fd())).ok();
+        }
+        None
+    }
+
+    fn is_watched(&self, path: &Path) -> bool {
+        self.roots.iter().any(|(root, watch)| {
+            if watch.is_recursive {
+                path.starts_with(root)
+            } else {
+                path == root || path.parent() == Some(root)
+            }
+        })
+    }
+
+    fn watch_infos(&self) -> Vec<WatchInfo> {
+        let mut infos: Vec<_> = self
+            .roots
+            .iter()
+            .map(|(path, root)| WatchInfo {
+                path: path.clone(),
+                recursive_mode: if root.is_recursive {
+                    RecursiveMode::Recursive
+                } else {
+                    RecursiveMode::NonRecursive
+                },
+                // the one mark the root uses, possibly shared with other roots
+                count: 1,
+                // pending paths are reported from their filesystem's mark
+                status: if path.exists() {
+                    WatchStatus::Active
+                } else {
+                    WatchStatus::Pending
+                },
+            })
+            .collect();
+        infos.sort_by(|a, b| a.path.cmp(&b.path));
+        infos
+    }
+
+    fn add_watch(&mut self, path: PathBuf, is_recursive: bool) -> Result<()> {
+        // Pending paths are watched through the filesystem of their nearest existing ancestor,
+        // which also keeps reporting them after they were removed and created again.
+        let (path, marked) = if self.pending_watches {
+            resolve_pending(&path).ok_or_else(|| Error::path_not_found().add_path(path))?
+        } else {
+            let path = path
+                .canonicalize()
+                .map_err(|e| Error::io(e).add_path(path.clone()))?;
+            (path.clone(), path)
+        };
+        let meta = fs::metadata(&marked).map_err(|e| Error::io(e).add_path(path.clone()))?;
+        let dev = meta.dev();
+
+        if let Some(root) = self.roots.remove(&path) {
+            self.release_mark(&path, &root);
+        }
+
+        let kind = if self.pending_watches || (is_recursive && meta.is_dir()) {
+            match self.acquire_mark(&marked, dev, MarkKind::Filesystem) {
+                Ok(()) => MarkKind::Filesystem,
+                // e.g. btrfs subvolumes can't be marked as a whole
+                Err(e) if is_fallback_error(&e) => {
+                    self.acquire_mark(&marked, dev, MarkKind::Mount)?;
+                    MarkKind::Mount
+                }
+                Err(e) => return Err(Error::io(e).add_path(path)),
+            }
+        } else {
+            self.acquire_mark(&path, dev, MarkKind::Inode)
+                .map_err(|e| Error::io(e).add_path(path.clone()))?;
+            MarkKind::Inode
+        };
+
+        self.roots.insert(
+            path,
+            WatchRoot {
+                is_recursive,
+                dev,
+                kind,
+            },
+        );
+        Ok(())
+    }
+
+    fn remove_watch(&mut self, path: PathBuf) -> Result<()> {
+        let path = match path.canonicalize() {
+            Ok(path) => path,
+            Err(_) => resolve_pending(&path).map_or(path, |(path, _)| path),
+        };
+        match self.roots.remove(&path) {
+            None => Err(Error::watch_not_found().add_path(path)),
+            Some(root) => {
+                self.release_mark(&path, &root);
+                Ok(())
+            }
+        }
+    }
+
+    fn mark_key(path: &Path, dev: u64, kind: MarkKind) -> (u64, Option<PathBuf>) {
+        match kind {
+            MarkKind::Inode => (dev, Some(path.to_path_buf())),
+            MarkKind::Filesystem | MarkKind::Mount => (dev, None),
+        }
+    }
+
+    fn acquire_mark(&mut self, path: &Path, dev: u64, kind: MarkKind) -> io::Result<()> {
+        let key = Self::mark_key(path, dev, kind);
+        if let Some(mark) = self.marks.get_mut(&key) {
+            if mark.kind == kind {
+                mark.refs += 1;
+                return Ok(());
+            }
+        }
+
+        let fanotify = match self.fanotify {
+            Some(ref fanotify) => fanotify.as_raw_fd(),
+            None => return Ok(()),
+        };
+        fanotify_mark(
+            fanotify,
+            libc::FAN_MARK_ADD | kind.flags(),
+            kind.mask(),
+            path,
+        )?;
+
+        // an `O_PATH` descriptor is not accepted by `open_by_handle_at`
+        let mount_fd = File::open(path)?;
+        let fsid = filesystem_id(&mount_fd);
+        self.marks.insert(
+            key,
+            Mark {
+                kind,
+                path: path.to_path_buf(),
+                refs: 1,
+                mount_fd,
+                fsid,
+            },
+        );
+        Ok(())
+    }
+
+    fn release_mark(&mut self, path: &Path, root: &WatchRoot) {
+        let key = Self::mark_key(path, root.dev, root.kind);
+        let remove = match self.marks.get_mut(&key) {
+            Some(mark) => {
+                mark.refs -= 1;
+                mark.refs == 0
+            }
+            None => false,
+        };
+        if remove {
+            if let (Some(mark), Some(fanotify)) = (self.marks.remove(&key), self.fanotify.as_ref())
+            {
+                let _ = fanotify_mark(
+                    fanotify.as_raw_fd(),
+                    libc::FAN_MARK_REMOVE | mark.kind.flags(),
+                    mark.kind.mask(),
+                    &mark.path,
+                );
+            }
+        }
+    }
+}
+
+fn fanotify_mark(fd: RawFd, flags: u32, mask: u64, path: &Path) -> io::Result<()> {
+    let c_path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let res = unsafe { libc::fanotify_mark(fd, flags, mask, libc::AT_FDCWD, c_path.as_ptr()) };
+    if res < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+/// Canonicalize a path that may not exist, through its nearest existing ancestor.
+///
+/// Returns the canonical path together with that ancestor.
+fn resolve_pending(path: &Path) -> Option<(PathBuf, PathBuf)> {
+    if let Ok(path) = path.canonicalize() {
+        return Some((path.clone(), path));
+    }
+    let existing = path.ancestors().skip(1).find(|a| a.exists())?;
+    let ancestor = existing.canonicalize().ok()?;
+    let rest = path.strip_prefix(existing).ok()?;
+    Some((ancestor.join(rest), ancestor))
+}
+
+/// Errors indicating the filesystem can't be marked as a whole, but its mount can
+fn is_fallback_error(e: &io::Error) -> bool {
+    matches!(
+        e.raw_os_error(),
+        Some(libc::EXDEV) | Some(libc::EINVAL) | Some(libc::ENODEV)
+    )
+}
+
+/// Filesystem ID as reported in fanotify information records
+fn filesystem_id(file: &File) -> Option<[i32; 2]> {
+    let mut stat: libc::statfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::fstatfs(file.as_raw_fd(), &mut stat) } < 0 {
+        return None;
+    }
+    // `fsid_t` keeps its two integers private
+    Some(unsafe { std::mem::transmute::<libc::fsid_t, [i32; 2]>(stat.f_fsid) })
+}
+
+impl FanotifyWatcher {
+    fn from_event_handler(
+        event_handler: Box<dyn EventHandler>,
+        filters: WatchFilters,
+        config: Config,
+    ) -> Result<Self> {
+        let fd = unsafe {
+            libc::fanotify_init(
+                libc::FAN_CLASS_NOTIF
+                    | libc::FAN_CLOEXEC
+                    | libc::FAN_NONBLOCK
+                    | libc::FAN_REPORT_DFID_NAME,
+                (libc::O_RDONLY | libc::O_CLOEXEC) as u32,
+            )
+        };
+        if fd < 0 {
+            let err = io::Error::last_os_error();
+            return Err(match err.raw_os_error() {
+                Some(libc::EPERM) => Error::generic(
+                    "fanotify requires the CAP_SYS_ADMIN capability, which this process lacks",
+                ),
+                Some(libc::EINVAL) => Error::generic(
+                    "fanotify with FAN_REPORT_DFID_NAME is not supported by this kernel (needs Linux 5.9)",
+                ),
+                _ => Error::io(err),
+            });
+        }
+        let fanotify = unsafe { File::from_raw_fd(fd) };
+
+        let event_loop = EventLoop::new(fanotify, event_handler, config)?;
+        let channel = event_loop.event_loop_tx.clone();
+        let waker = event_loop.event_loop_waker.clone();
+        event_loop.run();
+        Ok(FanotifyWatcher {
+            channel,
+            waker,
+            filters,
+        })
+    }
+
+    fn watch_inner(&mut self, path: &Path, options: WatchOptions) -> Result<()> {
+        let pb = if path.is_absolute() {
+            path.to_owned()
+        } else {
+            let p = env::current_dir().map_err(Error::io)?;
+            p.join(path)
+        };
+        self.filters.add(pb.clone(), options, || {
+            let (tx, rx) = unbounded();
+            let msg = EventLoopMsg::AddWatch(pb, options.recursive_mode(), tx);
+
+            // we expect the event loop to live and reply => unwraps must not panic
+            self.channel.send(msg).unwrap();
+            self.waker.wake().unwrap();
+            rx.recv().unwrap()
+        })
+    }
+
+    fn unwatch_inner(&mut self, path: &Path) -> Result<()> {
+        let pb = if path.is_absolute() {
+            path.to_owned()
+        } else {
+            let p = env::current_dir().map_err(Error::io)?;
+            p.join(path)
+        };
+        self.filters.remove(&pb);
+        let (tx, rx) = unbounded();
+        let msg = EventLoopMsg::RemoveWatch(pb, tx);
+
+        // we expect the event loop to live and reply => unwraps must not panic
+        self.channel.send(msg).unwrap();
+        self.waker.wake().unwrap();
+        rx.recv().unwrap()
+    }
+}
+
+impl Watcher for FanotifyWatcher {
+    /// Create a new watcher.
+    fn new<F: EventHandler>(event_handler: F, config: Config) -> Result<Self> {
+        let (event_handler, filters, _) = filtered_handler(event_handler, &config);
+        Self::from_event_handler(event_handler, filters, config)
+    }
+
+    fn watch(&mut self, path: &Path, recursive_mode: RecursiveMode) -> Result<()> {
+        self.watch_inner(path, WatchOptions::new(recursive_mode))
+    }
+
+    fn watch_with(&mut self, path: &Path, options: WatchOptions) -> Result<()> {
+        self.watch_inner(path, options)
+    }
+
+    fn unwatch(&mut self, path: &Path) -> Result<()> {
+        self.unwatch_inner(path)
+    }
+
+    fn watches(&self) -> Result<Vec<WatchInfo>> {
+        let (tx, rx) = unbounded();
+        self.channel.send(EventLoopMsg::Watches(tx))?;
+        self.waker.wake()?;
+        Ok(rx.recv()?)
+    }
+
+    fn kind() -> crate::WatcherKind {
+        crate::WatcherKind::Fanotify
+    }
+}
+
+impl Drop for FanotifyWatcher {
+    fn drop(&mut self) {
+        // we expect the event loop to live => unwrap must not panic
+        self.channel.send(EventLoopMsg::Shutdown).unwrap();
+        self.waker.wake().unwrap();
+    }
+}
+
+#[test]
+fn fanotify_watcher_is_send_and_sync() {
+    fn check<T: Send + Sync>() {}
+    check::<FanotifyWatcher>();
+}
+
+#[test]
+#[ignore = "needs CAP_SYS_ADMIN, run with --ignored as root"]
+fn fanotify_reports_process_id() {
+    use std::time::Duration;
+
+    let (tx, rx) = std::sync::mpsc::channel();
+    let mut watcher = FanotifyWatcher::new(tx, Config::default()).unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let dir_path = dir.path().canonicalize().unwrap();
+    watcher.watch(&dir_path, RecursiveMode::Recursive).unwrap();
+
+    let file = dir_path.join("file");
+    fs::write(&file, b"data").unwrap();
+
+    let event = std::iter::from_fn(|| rx.recv_timeout(Duration::from_secs(5)).ok())
+        .map(|res: Result<Event>| res.unwrap())
+        .find(|event| event.kind.is_create())
+        .expect("no create event received");
+    assert_eq!(event.paths, vec![file]);
+    assert_eq!(event.attrs.process_id(), Some(std::process::id()));
+}
+
+#[test]
+#[ignore = "needs CAP_SYS_ADMIN, run with --ignored as root"]
+fn fanotify_watches_pending_paths() {
+    use std::time::Duration;
+
+    let (tx, rx) = std::sync::mpsc::channel();
+    let config = Config::default().with_pending_watches(true);
+    let mut watcher = FanotifyWatcher::new(tx, config).unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let target = dir.path().canonicalize().unwrap().join("a").join("b");
+    watcher.watch(&target, RecursiveMode::Recursive).unwrap();
+    assert_eq!(watcher.watches().unwrap()[0].status, WatchStatus::Pending);
+
+    fs::create_dir_all(&target).unwrap();
+    fs::write(target.join("file"), b"data").unwrap();
+    std::iter::from_fn(|| rx.recv_timeout(Duration::from_secs(5)).ok())
+        .map(|res: Result<Event>| res.unwrap())
+        .find(|event| event.kind.is_create() && event.paths == [target.join("file")])
+        .expect("no create event received");
+    assert_eq!(watcher.watches().unwrap()[0].status, WatchStatus::Active);
+}
@@ -0,0 +1,141 @@
+//! Event kinds of single watches, see `Watcher::watch_with`
+//!
+//! Backends that can't subscribe to some kinds of events only, drop the others in `FilterHandler`
+//! before they reach the event handler.
+
+use crate::{
+    Config, Error, Event, EventHandler, IgnoreRules, RecursiveMode, Result, WatchInfo,
+    WatchOptions, WatchStatus,
+};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Returns the options of the nearest path in `watches` whose watch covers `path`
+pub(crate) fn nearest_watch<'a>(
+    watches: &'a HashMap<PathBuf, WatchOptions>,
+    path: &Path,
+) -> Option<&'a WatchOptions> {
+    path.ancestors().find_map(|dir| {
+        watches.get(dir).filter(|options| {
+            dir == path || options.recursive_mode().is_recursive() || path.parent() == Some(dir)
+        })
+    })
+}
+
+/// Wrap `handler` for a backend reporting every kind of event, dropping those of paths ignored by
+/// git and of kinds no watch asks for.
+///
+/// Returns the handler, the filters to keep the watches in, and the git ignore rules for backends
+/// to skip ignored paths altogether.
+pub(crate) fn filtered_handler<H: EventHandler>(
+    handler: H,
+    config: &Config,
+) -> (Box<dyn EventHandler>, WatchFilters, Option<IgnoreRules>) {
+    let filters = WatchFilters::default();
+    let (handler, ignore) = crate::gitignore::wrap_handler(handler, config, &filters);
+    (filters.wrap_handler(handler), filters, ignore)
+}
+
+/// Options of the watched paths, shared with the handler filtering their events
+#[derive(Debug, Clone, Default)]
+pub(crate) struct WatchFilters(Arc<Mutex<HashMap<PathBuf, WatchOptions>>>);
+
+impl WatchFilters {
+    /// Drop the events of `handler` that no watch asks for
+    pub(crate) fn wrap_handler<H: EventHandler>(&self, handler: H) -> Box<dyn EventHandler> {
+        Box::new(FilterHandler {
+            filters: self.clone(),
+            handler,
+        })
+    }
+
+    /// Filter the events of `path` by `options` while `watch` watches it.
+    ///
+    /// The filter is in place before the first event arrives, and the previous one is restored
+    /// if `watch` fails.
+    pub(crate) fn add<F>(&self, path: PathBuf, options: WatchOptions, watch: F) -> Result<()>
+    where
+        F: FnOnce() -> Result<()>,
+    {
+        let previous = self.lock().insert(path.clone(), options);
+        let res = watch();
+        if res.is_err() {
+            let mut filters = self.lock();
+            match previous {
+                Some(previous) => filters.insert(path, previous),
+                None => filters.remove(&path),
+            };
+        }
+        res
+    }
+
+    /// Stop filtering the events of `path`
+    pub(crate) fn remove(&self, path: &Path) {
+        self.lock().remove(path);
+    }
+
+    /// Stop filtering the events of `path`, failing if it isn't watched.
+    ///
+    /// For backends that only keep track of their watches here.
+    pub(crate) fn unwatch(&self, path: &Path) -> Result<()> {
+        match self.lock().remove(path) {
+            Some(_) => Ok(()),
+            None => Err(Error::watch_not_found().add_path(path.to_path_buf())),
+        }
+    }
+
+    /// Describe the watched paths for [`crate::Watcher::watches`], sorted by path.
+    ///
+    /// Backends without pending watches only have active ones, `count` tells how many kernel
+    /// watches each of them uses.
+    pub(crate) fn watch_infos<F>(&self, mut count: F) -> Vec<WatchInfo>
+    where
+        F: FnMut(&Path, RecursiveMode) -> usize,
+    {
+        let mut infos: Vec<_> = self
+            .lock()
+            .iter()
+            .map(|(path, options)| WatchInfo {
+                path: path.clone(),
+                recursive_mode: options.recursive_mode(),
+                count: count(path, options.recursive_mode()),
+                status: WatchStatus::Active,
+            })
+            .collect();
+        infos.sort_by(|a, b| a.path.cmp(&b.path));
+        infos
+    }
+
+    /// Whether the nearest watch of one of the paths of `event` asks for its kind
+    ///
+    /// Events without paths, and those of paths no watch covers, always pass.
+    fn matches(&self, event: &Event) -> bool {
+        let filters = self.lock();
+        event.paths.is_empty()
+            || event.paths.iter().any(|path| {
+                nearest_watch(&filters, path)
+                    .and_then(WatchOptions::event_kinds)
+                    .map_or(true, |kinds| kinds.matches(&event.kind))
+            })
+    }
+
+    pub(crate) fn lock(&self) -> MutexGuard<'_, HashMap<PathBuf, WatchOptions>> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Passes on the events matching the [WatchFilters]
+struct FilterHandler<H> {
+    filters: WatchFilters,
+    handler: H,
+}
+
+impl<H: EventHandler> EventHandler for FilterHandler<H> {
+    fn handle_event(&mut self, event: Result<Event>) {
+        match event {
+            Ok(ref e) if !self.filters.matches(e) => {}
+            event => self.handler.handle_event(event),
+        }
+    }
+}
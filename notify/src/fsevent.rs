@@ -15,7 +15,11 @@
 #![allow(non_upper_case_globals, dead_code)]
 
 use crate::event::*;
-use crate::{unbounded, Config, Error, EventHandler, RecursiveMode, Result, Sender, Watcher};
+use crate::filter::{filtered_handler, WatchFilters};
+use crate::{
+    unbounded, Config, Error, EventHandler, RecursiveMode, Result, Sender, WatchInfo, WatchOptions,
+    Watcher,
+};
 use fsevent_sys as fs;
 use fsevent_sys::core_foundation as cf;
 use std::collections::HashMap;
@@ -66,6 +70,7 @@ pub struct FsEventWatcher {
     event_handler: Arc<Mutex<dyn EventHandler>>,
     runloop: Option<(cf::CFRunLoopRef, thread::JoinHandle<()>)>,
     recursive_info: HashMap<PathBuf, bool>,
+    filters: WatchFilters,
 }
 
 impl fmt::Debug for FsEventWatcher {
@@ -78,6 +83,7 @@ impl fmt::Debug for FsEventWatcher {
             .field("event_handler", &Arc::as_ptr(&self.event_handler))
             .field("runloop", &self.runloop)
             .field("recursive_info", &self.recursive_info)
+            .field("filters", &self.filters)
             .finish()
     }
 }
@@ -265,7 +271,10 @@ extern "C" {
 }
 
 impl FsEventWatcher {
-    fn from_event_handler(event_handler: Arc<Mutex<dyn EventHandler>>) -> Result<Self> {
+    fn from_event_handler(
+        event_handler: Arc<Mutex<dyn EventHandler>>,
+        filters: WatchFilters,
+    ) -> Result<Self> {
         Ok(FsEventWatcher {
             paths: unsafe {
                 cf::CFArrayCreateMutable(cf::kCFAllocatorDefault, 0, &cf::kCFTypeArrayCallBacks)
@@ -276,12 +285,18 @@ impl FsEventWatcher {
             event_handler,
             runloop: None,
             recursive_info: HashMap::new(),
+            filters,
         })
     }
 
-    fn watch_inner(&mut self, path: &Path, recursive_mode: RecursiveMode) -> Result<()> {
+    fn watch_inner(&mut self, path: &Path, options: WatchOptions) -> Result<()> {
         self.stop();
-        let result = self.append_path(path, recursive_mode);
+        // events are reported with canonical paths
+        let canonical_path = path.canonicalize().unwrap_or_else(|_| path.to_owned());
+        let filters = self.filters.clone();
+        let result = filters.add(canonical_path, options, || {
+            self.append_path(path, options.recursive_mode())
+        });
         // ignore return error: may be empty path list
         let _ = self.run();
         result
@@ -352,6 +367,7 @@ impl FsEventWatcher {
         } else {
             path.to_owned()
         };
+        self.filters.remove(&p);
         match self.recursive_info.remove(&p) {
             Some(_) => Ok(()),
             None => Err(Error::watch_not_found()),
@@ -543,12 +559,17 @@ unsafe fn callback_impl(
 
 impl Watcher for FsEventWatcher {
     /// Create a new watcher.
-    fn new<F: EventHandler>(event_handler: F, _config: Config) -> Result<Self> {
-        Self::from_event_handler(Arc::new(Mutex::new(event_handler)))
+    fn new<F: EventHandler>(event_handler: F, config: Config) -> Result<Self> {
+        let (event_handler, filters, _) = filtered_handler(event_handler, &config);
+        Self::from_event_handler(Arc::new(Mutex::new(event_handler)), filters)
     }
 
     fn watch(&mut self, path: &Path, recursive_mode: RecursiveMode) -> Result<()> {
-        self.watch_inner(path, recursive_mode)
+        self.watch_inner(path, WatchOptions::new(recursive_mode))
+    }
+
+    fn watch_with(&mut self, path: &Path, options: WatchOptions) -> Result<()> {
+        self.watch_inner(path, options)
     }
 
     fn unwatch(&mut self, path: &Path) -> Result<()> {
@@ -561,6 +582,11 @@ impl Watcher for FsEventWatcher {
         rx.recv()?
     }
 
+    fn watches(&self) -> Result<Vec<WatchInfo>> {
+        // a single stream covers all watched paths
+        Ok(self.filters.watch_infos(|_, _| 1))
+    }
+
     fn kind() -> crate::WatcherKind {
         crate::WatcherKind::Fsevent
     }
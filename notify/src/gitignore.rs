@@ -0,0 +1,319 @@
+//! Git ignore rules, see `Config::with_gitignore`
+//!
+//! Rules are read from `.gitignore` and `.ignore` files in every directory, and from
+//! `.git/info/exclude` at the root of a repository. Like git, deeper files take precedence and
+//! lookups stop at the repository root, or at the watched path outside of repositories. Rules and
+//! repository roots are cached per directory and reloaded whenever an event for one of these files,
+//! or for a `.git` directory, passes through `GitIgnoreHandler`.
+
+#[cfg(feature = "gitignore")]
+use crate::event::{CreateKind, EventKind, RemoveKind};
+use crate::filter::WatchFilters;
+use crate::{Config, EventHandler, IgnoreRules};
+#[cfg(feature = "gitignore")]
+use crate::{Event, Result};
+#[cfg(feature = "gitignore")]
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+#[cfg(any(target_os = "linux", feature = "gitignore"))]
+use std::path::Path;
+#[cfg(feature = "gitignore")]
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+/// Filter the events of `handler` by git ignore rules, if `config` asks for it.
+///
+/// `roots` are the watched paths, whose ancestors' rules are only read inside a repository.
+/// Returns the handler to use and the rules, for backends to skip ignored paths altogether.
+#[cfg(feature = "gitignore")]
+pub(crate) fn wrap_handler<H: EventHandler>(
+    handler: H,
+    config: &Config,
+    roots: &WatchFilters,
+) -> (Box<dyn EventHandler>, Option<IgnoreRules>) {
+    if !config.gitignore() {
+        return (Box::new(handler), None);
+    }
+    let gitignore = Arc::new(Mutex::new(GitIgnore {
+        roots: roots.clone(),
+        ..GitIgnore::default()
+    }));
+    let rules = {
+        let gitignore = Arc::clone(&gitignore);
+        IgnoreRules::new(move |path| {
+            let mut gitignore = gitignore.lock().unwrap_or_else(|e| e.into_inner());
+            gitignore.is_ignored(path, path.is_dir())
+        })
+    };
+    (
+        Box::new(GitIgnoreHandler { gitignore, handler }),
+        Some(rules),
+    )
+}
+
+#[cfg(not(feature = "gitignore"))]
+pub(crate) fn wrap_handler<H: EventHandler>(
+    handler: H,
+    _config: &Config,
+    _roots: &WatchFilters,
+) -> (Box<dyn EventHandler>, Option<IgnoreRules>) {
+    (Box::new(handler), None)
+}
+
+/// Returns the directory whose rules `path` holds, if it is an ignore file
+#[cfg(any(target_os = "linux", feature = "gitignore"))]
+pub(crate) fn ignore_file_dir(path: &Path) -> Option<&Path> {
+    match path.file_name().and_then(|name| name.to_str()) {
+        Some(".gitignore") | Some(".ignore") => path.parent(),
+        Some("exclude") if path.ends_with(".git/info/exclude") => {
+            path.parent().and_then(Path::parent).and_then(Path::parent)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(feature = "gitignore")]
+#[derive(Debug, Default)]
+struct GitIgnore {
+    /// Rules of each directory, from its own ignore files only
+    rules: HashMap<PathBuf, Gitignore>,
+    /// Whether a directory is ignored, including through one of its parents
+    dirs: HashMap<PathBuf, bool>,
+    /// The repository each directory is in, by its root
+    repositories: HashMap<PathBuf, Option<PathBuf>>,
+    /// The watched paths
+    roots: WatchFilters,
+}
+
+#[cfg(feature = "gitignore")]
+impl GitIgnore {
+    /// Returns whether `path` or one of its parents is ignored
+    fn is_ignored(&mut self, path: &Path, is_dir: bool) -> bool {
+        if is_git_dir(path) {
+            return true;
+        }
+        let parent = match path.parent() {
+            Some(parent) => parent,
+            None => return false,
+        };
+        match self.scope(parent) {
+            Some(top) => self.is_dir_ignored(parent, &top) || self.matched(path, is_dir, &top),
+            None => false,
+        }
+    }
+
+    /// Forget the rules read from `path` if it is an ignore file, or the repository roots if it
+    /// is a `.git` directory.
+    ///
+    /// Returns whether it was one of them.
+    fn reload(&mut self, path: &Path) -> bool {
+        if is_git_dir(path) {
+            self.repositories.clear();
+            self.dirs.clear();
+            return true;
+        }
+        match ignore_file_dir(path) {
+            Some(dir) => {
+                self.rules.remove(dir);
+                self.dirs.clear();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Whether `dir`, at or below `top`, is ignored by the rules up to `top`
+    fn is_dir_ignored(&mut self, dir: &Path, top: &Path) -> bool {
+        if let Some(&ignored) = self.dirs.get(dir) {
+            return ignored;
+        }
+        let ignored = match dir.parent() {
+            Some(parent) if dir != top => {
+                is_git_dir(dir) || self.is_dir_ignored(parent, top) || self.matched(dir, true, top)
+            }
+            _ => false,
+        };
+        self.dirs.insert(dir.to_path_buf(), ignored);
+        ignored
+    }
+
+    /// Match `path` against the rules of its parents up to `top`, the nearest deciding
+    fn matched(&mut self, path: &Path, is_dir: bool, top: &Path) -> bool {
+        for dir in path.ancestors().skip(1) {
+            let matched = self.rules(dir).matched(path, is_dir);
+            if matched.is_ignore() {
+                return true;
+            }
+            if matched.is_whitelist() || dir == top {
+                return false;
+            }
+        }
+        false
+    }
+
+    /// Returns the directory where rule lookups for the entries of `dir` stop: the root of its
+    /// repository, or outside of repositories the outermost watched path containing it.
+    ///
+    /// Outside of both, no rules apply.
+    fn scope(&mut self, dir: &Path) -> Option<PathBuf> {
+        if let Some(root) = self.repository(dir) {
+            return Some(root);
+        }
+        let roots = self.roots.lock();
+        dir.ancestors()
+            .filter(|dir| roots.contains_key(*dir))
+            .last()
+            .map(Path::to_path_buf)
+    }
+
+    /// Returns the root of the repository `dir` is in, checking each directory once
+    fn repository(&mut self, dir: &Path) -> Option<PathBuf> {
+        if let Some(root) = self.repositories.get(dir) {
+            return root.clone();
+        }
+        let root = if dir.join(".git").exists() {
+            Some(dir.to_path_buf())
+        } else {
+            dir.parent().and_then(|parent| self.repository(parent))
+        };
+        self.repositories.insert(dir.to_path_buf(), root.clone());
+        root
+    }
+
+    fn rules(&mut self, dir: &Path) -> &Gitignore {
+        self.rules.entry(dir.to_path_buf()).or_insert_with(|| {
+            let mut builder = GitignoreBuilder::new(dir);
+            // later files take precedence
+            for file in [".git/info/exclude", ".gitignore", ".ignore"] {
+                let file = dir.join(file);
+                if file.is_file() {
+                    // invalid lines are skipped, like git does
+                    let _ = builder.add(file);
+                }
+            }
+            builder.build().unwrap_or_else(|_| Gitignore::empty())
+        })
+    }
+}
+
+#[cfg(feature = "gitignore")]
+fn is_git_dir(path: &Path) -> bool {
+    path.file_name().map_or(false, |name| name == ".git")
+}
+
+/// Whether `path` of an event of `kind` is a directory, from the kind where it tells
+#[cfg(feature = "gitignore")]
+fn is_dir(kind: &EventKind, path: &Path) -> bool {
+    match kind {
+        EventKind::Create(CreateKind::Folder) | EventKind::Remove(RemoveKind::Folder) => true,
+        EventKind::Create(CreateKind::File) | EventKind::Remove(RemoveKind::File) => false,
+        _ => path.is_dir(),
+    }
+}
+
+/// [`EventHandler`] dropping events about ignored paths and reloading changed ignore files
+#[cfg(feature = "gitignore")]
+struct GitIgnoreHandler<H> {
+    gitignore: Arc<Mutex<GitIgnore>>,
+    handler: H,
+}
+
+#[cfg(feature = "gitignore")]
+impl<H: EventHandler> EventHandler for GitIgnoreHandler<H> {
+    fn handle_event(&mut self, event: Result<Event>) {
+        if let Ok(ref event) = event {
+            let mut gitignore = self.gitignore.lock().unwrap_or_else(|e| e.into_inner());
+            for path in &event.paths {
+                gitignore.reload(path);
+            }
+            // kept if any path is not ignored, e.g. a rename out of an ignored directory
+            if !event.paths.is_empty()
+                && event
+                    .paths
+                    .iter()
+                    .all(|path| gitignore.is_ignored(path, is_dir(&event.kind, path)))
+            {
+                return;
+            }
+        }
+        self.handler.handle_event(event);
+    }
+}
+
+#[cfg(all(test, feature = "gitignore"))]
+mod tests {
+    use super::*;
+    use crate::{RecursiveMode, WatchOptions};
+    use std::fs;
+
+    #[test]
+    fn nested_rules_and_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join(".git/info")).unwrap();
+        fs::create_dir_all(root.join("src/gen")).unwrap();
+        fs::write(root.join(".gitignore"), "target/\n*.log\n").unwrap();
+        fs::write(root.join("src/.gitignore"), "!keep.log\ngen\n").unwrap();
+        fs::write(root.join(".git/info/exclude"), "scratch\n").unwrap();
+
+        let mut gitignore = GitIgnore::default();
+        assert!(gitignore.is_ignored(&root.join("target"), true));
+        assert!(gitignore.is_ignored(&root.join("target/debug/out"), false));
+        assert!(gitignore.is_ignored(&root.join("a.log"), false));
+        assert!(!gitignore.is_ignored(&root.join("src/keep.log"), false));
+        assert!(gitignore.is_ignored(&root.join("src/gen/mod.rs"), false));
+        assert!(gitignore.is_ignored(&root.join("scratch"), false));
+        assert!(gitignore.is_ignored(&root.join(".git/index"), false));
+        assert!(!gitignore.is_ignored(&root.join("src/lib.rs"), false));
+
+        fs::write(root.join(".gitignore"), "").unwrap();
+        assert!(gitignore.reload(&root.join(".gitignore")));
+        assert!(!gitignore.is_ignored(&root.join("a.log"), false));
+    }
+
+    #[test]
+    fn rules_above_watched_path_outside_repository() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::write(dir.path().join(".gitignore"), "*.log\n").unwrap();
+        fs::write(root.join(".gitignore"), "*.tmp\n").unwrap();
+
+        let mut gitignore = GitIgnore::default();
+        let options = WatchOptions::new(RecursiveMode::Recursive);
+        gitignore
+            .roots
+            .add(root.clone(), options, || Ok(()))
+            .unwrap();
+        assert!(gitignore.is_ignored(&root.join("sub/a.tmp"), false));
+        assert!(!gitignore.is_ignored(&root.join("sub/a.log"), false));
+        assert!(!gitignore.rules.contains_key(dir.path()));
+
+        // inside a repository, its rules apply up to its root
+        fs::create_dir(dir.path().join(".git")).unwrap();
+        assert!(gitignore.reload(&dir.path().join(".git")));
+        assert!(gitignore.is_ignored(&root.join("sub/a.log"), false));
+    }
+
+    #[test]
+    fn removed_directories_matched_as_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir(root.join(".git")).unwrap();
+        fs::write(root.join(".gitignore"), "build/\n").unwrap();
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        let mut handler = GitIgnoreHandler {
+            gitignore: Arc::default(),
+            handler: tx,
+        };
+        let removed = |kind| Ok(Event::new(EventKind::Remove(kind)).add_path(root.join("build")));
+        handler.handle_event(removed(RemoveKind::Folder));
+        handler.handle_event(removed(RemoveKind::File));
+        let events: Vec<_> = rx.try_iter().map(Result::unwrap).collect();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].kind, EventKind::Remove(RemoveKind::File));
+    }
+}
@@ -0,0 +1,233 @@
+//! Content hashes of `PollWatcher`, see `Config::with_hash_strategy`
+
+use std::fmt;
+use std::fs::File;
+use std::hash::{Hash, Hasher};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Size of the reads when hashing files
+const BUF_SIZE: usize = 64 * 1024;
+
+/// Computes the content hashes of files, see [HashStrategy::custom]
+///
+/// Hashes are compared with those of earlier scans and, with a
+/// [snapshot file](crate::Config::with_snapshot_file), of earlier runs. They have to be
+/// deterministic, so hashers seeded at random like the one of `HashMap` don't fit.
+pub trait ContentHasher: Send + Sync {
+    /// Returns the hash of the file at `path`, `size` bytes long, or `None` to leave it unhashed
+    fn hash_file(&self, path: &Path, size: u64) -> io::Result<Option<u64>>;
+}
+
+/// How [crate::PollWatcher] hashes file contents, see [crate::Config::with_hash_strategy]
+///
+/// Two strategies are only equal if they are clones of each other.
+#[derive(Clone)]
+pub struct HashStrategy(Arc<dyn ContentHasher>);
+
+impl HashStrategy {
+    /// Hash whole files, with a fast non-cryptographic hash
+    pub fn full() -> Self {
+        Self::custom(Full)
+    }
+
+    /// Hash the size of files and only their first and last `len` bytes
+    ///
+    /// Much cheaper for large files, but changes in the middle of them are only found by their
+    /// modification time.
+    pub fn head_tail(len: u64) -> Self {
+        Self::custom(HeadTail(len))
+    }
+
+    /// Leave files over `max_size` bytes unhashed, hash the others as this strategy does
+    ///
+    /// Changes to the files left out are only found by their size and modification time.
+    pub fn size_limit(self, max_size: u64) -> Self {
+        Self::custom(SizeLimit {
+            strategy: self,
+            max_size,
+        })
+    }
+
+    /// Hash with a custom `hasher`
+    pub fn custom<H: ContentHasher + 'static>(hasher: H) -> Self {
+        HashStrategy(Arc::new(hasher))
+    }
+
+    /// Returns the hash of the file at `path`, `size` bytes long, if it is hashed
+    pub fn hash_file(&self, path: &Path, size: u64) -> io::Result<Option<u64>> {
+        self.0.hash_file(path, size)
+    }
+}
+
+impl Default for HashStrategy {
+    fn default() -> Self {
+        Self::full()
+    }
+}
+
+impl PartialEq for HashStrategy {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for HashStrategy {}
+
+impl Hash for HashStrategy {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (Arc::as_ptr(&self.0) as *const () as usize).hash(state);
+    }
+}
+
+impl fmt::Debug for HashStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("HashStrategy").finish()
+    }
+}
+
+struct Full;
+
+impl ContentHasher for Full {
+    fn hash_file(&self, path: &Path, _size: u64) -> io::Result<Option<u64>> {
+        let mut file = File::open(path)?;
+        let mut hasher = StableHasher::default();
+        // read to the end, as the file may have grown since `size` was read
+        let mut buf = vec![0; BUF_SIZE];
+        hash_reader(&mut hasher, &mut file, &mut buf, u64::MAX)?;
+        Ok(Some(hasher.finish()))
+    }
+}
+
+struct HeadTail(u64);
+
+impl ContentHasher for HeadTail {
+    fn hash_file(&self, path: &Path, size: u64) -> io::Result<Option<u64>> {
+        let len = self.0;
+        let mut file = File::open(path)?;
+        let mut hasher = StableHasher::default();
+        hasher.write_u64(size);
+        let mut buf = buffer(len.min(size));
+        if size <= len.saturating_mul(2) {
+            hash_reader(&mut hasher, &mut file, &mut buf, size)?;
+        } else {
+            hash_reader(&mut hasher, &mut file, &mut buf, len)?;
+            file.seek(SeekFrom::Start(size - len))?;
+            hash_reader(&mut hasher, &mut file, &mut buf, len)?;
+        }
+        Ok(Some(hasher.finish()))
+    }
+}
+
+struct SizeLimit {
+    strategy: HashStrategy,
+    max_size: u64,
+}
+
+impl ContentHasher for SizeLimit {
+    fn hash_file(&self, path: &Path, size: u64) -> io::Result<Option<u64>> {
+        if size > self.max_size {
+            return Ok(None);
+        }
+        self.strategy.hash_file(path, size)
+    }
+}
+
+/// A read buffer for the `size` bytes of a read of fixed length, up to [BUF_SIZE]
+///
+/// Its length is a multiple of 8, so the words hashed don't depend on it.
+fn buffer(size: u64) -> Vec<u8> {
+    let len = (size.min(BUF_SIZE as u64) as usize + 7) & !7;
+    vec![0; len.max(8)]
+}
+
+/// Hash up to `len` bytes of `reader`, filling `buf` for every write but the last
+fn hash_reader(
+    hasher: &mut StableHasher,
+    reader: &mut impl Read,
+    buf: &mut [u8],
+    len: u64,
+) -> io::Result<()> {
+    let mut left = len;
+    while left > 0 {
+        let want = buf.len().min(usize::try_from(left).unwrap_or(usize::MAX));
+        let mut filled = 0;
+        while filled < want {
+            match reader.read(&mut buf[filled..want]) {
+                Ok(0) => break,
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        hasher.write(&buf[..filled]);
+        if filled < want {
+            break;
+        }
+        left -= filled as u64;
+    }
+    Ok(())
+}
+
+/// FxHash over 8 byte words with a final mix, the same across runs and platforms
+#[derive(Default)]
+struct StableHasher {
+    hash: u64,
+    len: u64,
+}
+
+impl StableHasher {
+    fn add(&mut self, word: u64) {
+        self.hash = (self.hash.rotate_left(5) ^ word).wrapping_mul(0x517c_c1b7_2722_0a95);
+    }
+}
+
+impl Hasher for StableHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        let mut words = bytes.chunks_exact(8);
+        for word in &mut words {
+            self.add(u64::from_le_bytes(word.try_into().unwrap()));
+        }
+        let rest = words.remainder();
+        if !rest.is_empty() {
+            let mut word = [0; 8];
+            word[..rest.len()].copy_from_slice(rest);
+            self.add(u64::from_le_bytes(word));
+        }
+        self.len += bytes.len() as u64;
+    }
+
+    fn finish(&self) -> u64 {
+        // murmur3's finalizer, so that every input bit affects every output bit
+        let mut hash = self.hash ^ self.len;
+        hash ^= hash >> 33;
+        hash = hash.wrapping_mul(0xff51_afd7_ed55_8ccd);
+        hash ^= hash >> 33;
+        hash = hash.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+        hash ^ (hash >> 33)
+    }
+}
+
+#[test]
+fn hashes_are_stable() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("file");
+    let content: Vec<u8> = (0..BUF_SIZE * 2 + 3).map(|i| (i % 251) as u8).collect();
+    std::fs::write(&path, &content).unwrap();
+    let size = content.len() as u64;
+
+    // the same whatever the size of the reads
+    let mut hasher = StableHasher::default();
+    hasher.write(&content);
+    let full = HashStrategy::full().hash_file(&path, size).unwrap();
+    assert_eq!(full, Some(hasher.finish()));
+    // saved in snapshots, so it must not change between versions either
+    assert_eq!(full, Some(0x1b39_c16c_22a9_75b6));
+    assert_eq!(HashStrategy::full().hash_file(&path, 0).unwrap(), full);
+
+    let head_tail = HashStrategy::head_tail(100);
+    assert_ne!(head_tail.hash_file(&path, size).unwrap(), full);
+    let limited = HashStrategy::full().size_limit(size - 1);
+    assert_eq!(limited.hash_file(&path, size).unwrap(), None);
+}
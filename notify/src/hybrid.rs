@@ -0,0 +1,85 @@
+//! Watcher using inotify where possible and polling beyond the inotify watch limit
+//!
+//! Every directory of a recursive inotify watch takes one of the `fs.inotify.max_user_watches`
+//! watches. When they run out, the [`HybridWatcher`] polls the rest of the subtree instead of
+//! failing, so large trees can be watched without raising the limit.
+
+use crate::{
+    Config, Event, EventHandler, INotifyWatcher, PollWatcher, RecursiveMode, Result, WatchInfo,
+    WatchOptions, Watcher,
+};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+pub use crate::inotify::WatchedPaths;
+
+/// Watcher implementation based on inotify, polling where the watch limit is reached
+///
+/// Polling uses the `poll_interval` and `compare_contents` of the [`Config`]. Events of both
+/// sources are delivered to the same handler.
+#[derive(Debug)]
+pub struct HybridWatcher {
+    inotify: INotifyWatcher,
+}
+
+impl HybridWatcher {
+    /// Returns the paths watched with inotify and the directories polled instead
+    pub fn watched_paths(&self) -> Result<WatchedPaths> {
+        self.inotify.watched_paths()
+    }
+}
+
+impl Watcher for HybridWatcher {
+    fn new<F: EventHandler>(event_handler: F, config: Config) -> Result<Self> {
+        let event_handler = SharedHandler(Arc::new(Mutex::new(event_handler)));
+        let fallback = PollWatcher::new(event_handler.clone(), config.clone())?;
+        let inotify = INotifyWatcher::with_fallback(Box::new(event_handler), config, fallback)?;
+        Ok(HybridWatcher { inotify })
+    }
+
+    fn watch(&mut self, path: &Path, recursive_mode: RecursiveMode) -> Result<()> {
+        self.inotify.watch(path, recursive_mode)
+    }
+
+    fn watch_with(&mut self, path: &Path, options: WatchOptions) -> Result<()> {
+        self.inotify.watch_with(path, options)
+    }
+
+    fn unwatch(&mut self, path: &Path) -> Result<()> {
+        self.inotify.unwatch(path)
+    }
+
+    fn configure(&mut self, config: Config) -> Result<bool> {
+        self.inotify.configure(config)
+    }
+
+    fn watches(&self) -> Result<Vec<WatchInfo>> {
+        self.inotify.watches()
+    }
+
+    fn kind() -> crate::WatcherKind {
+        crate::WatcherKind::Hybrid
+    }
+}
+
+/// Handler shared by the inotify event loop and the poll watcher
+struct SharedHandler<F>(Arc<Mutex<F>>);
+
+impl<F> Clone for SharedHandler<F> {
+    fn clone(&self) -> Self {
+        SharedHandler(Arc::clone(&self.0))
+    }
+}
+
+impl<F: EventHandler> EventHandler for SharedHandler<F> {
+    fn handle_event(&mut self, event: Result<Event>) {
+        let mut handler = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        handler.handle_event(event);
+    }
+}
+
+#[test]
+fn hybrid_watcher_is_send_and_sync() {
+    fn check<T: Send + Sync>() {}
+    check::<HybridWatcher>();
+}
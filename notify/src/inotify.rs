@@ -5,19 +5,25 @@
 //! will return events for the directory itself, and for files inside the directory.
 
 use super::event::*;
-use super::{Config, Error, ErrorKind, EventHandler, RecursiveMode, Result, Watcher};
+use super::poll::data::{DataBuilder, WatchData};
+use super::{
+    Config, Error, ErrorKind, EventHandler, EventKindMask, IgnoreRules, PollWatcher, RecursiveMode,
+    Result, SymlinkPolicy, WatchInfo, WatchOptions, WatchStatus, Watcher,
+};
+use crate::filter::{nearest_watch, WatchFilters};
 use crate::{bounded, unbounded, BoundSender, Receiver, Sender};
 use inotify as inotify_sys;
 use inotify_sys::{EventMask, Inotify, WatchDescriptor, WatchMask};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::env;
 use std::ffi::OsStr;
-use std::fs::metadata;
+use std::fs::{self, metadata};
+use std::io;
 use std::os::unix::io::AsRawFd;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use walkdir::WalkDir;
 
 const INOTIFY: mio::Token = mio::Token(0);
@@ -38,7 +44,47 @@ struct EventLoop {
     event_handler: Box<dyn EventHandler>,
     watches: HashMap<PathBuf, (WatchDescriptor, WatchMask, bool)>,
     paths: HashMap<WatchDescriptor, PathBuf>,
-    rename_event: Option<Event>,
+    /// Paths given to `watch`, and how they are watched
+    roots: HashMap<PathBuf, WatchOptions>,
+    /// `MOVED_FROM` events waiting for their `MOVED_TO`, with their deadline
+    rename_events: VecDeque<(Instant, Event)>,
+    rename_window: Duration,
+    pending_watches: bool,
+    /// Watched paths that don't exist (yet), see [`Config::with_pending_watches`]
+    pending: HashMap<PathBuf, PendingWatch>,
+    /// Ancestors of pending paths, watched for them to appear, with their number of users
+    helpers: HashMap<PathBuf, (WatchDescriptor, usize)>,
+    helper_paths: HashMap<WatchDescriptor, PathBuf>,
+    /// Directories not to place watches in, see `Config::with_gitignore`
+    ignore: Option<IgnoreRules>,
+    event_kinds: EventKindMask,
+    symlink_policy: SymlinkPolicy,
+    recovery: Option<Recovery>,
+    fallback: Option<Fallback>,
+    /// Places a watch, replaced in tests to run out of watches
+    add_watch: fn(&mut Inotify, &Path, WatchMask) -> io::Result<WatchDescriptor>,
+}
+
+/// Polling of the directories inotify ran out of watches for, see [`crate::HybridWatcher`]
+struct Fallback {
+    watcher: PollWatcher,
+    /// Polled directories, and whether they are polled recursively
+    polled: HashMap<PathBuf, bool>,
+}
+
+/// Snapshots of the watched trees, see [`Config::with_overflow_recovery`]
+struct Recovery {
+    data_builder: DataBuilder,
+    snapshots: HashMap<PathBuf, WatchData>,
+    /// Events found by rescans
+    rx: Receiver<Result<Event>>,
+}
+
+/// A watched path that doesn't exist at the moment
+struct PendingWatch {
+    is_recursive: bool,
+    /// Nearest existing ancestor, watched for the path to appear
+    ancestor: PathBuf,
 }
 
 /// Watcher implementation based on inotify
@@ -46,22 +92,34 @@ struct EventLoop {
 pub struct INotifyWatcher {
     channel: Sender<EventLoopMsg>,
     waker: Arc<mio::Waker>,
+    /// The watched paths, for the git ignore rules. Events are filtered by inotify itself.
+    roots: WatchFilters,
 }
 
 enum EventLoopMsg {
-    AddWatch(PathBuf, RecursiveMode, Sender<Result<()>>),
+    AddWatch(PathBuf, WatchOptions, Sender<Result<()>>),
     RemoveWatch(PathBuf, Sender<Result<()>>),
     Shutdown,
-    RenameTimeout(usize),
     Configure(Config, BoundSender<Result<bool>>),
+    WatchedPaths(Sender<WatchedPaths>),
+    Watches(Sender<Vec<WatchInfo>>),
+}
+
+/// Paths watched by a [`crate::HybridWatcher`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WatchedPaths {
+    /// Paths with an inotify watch
+    pub native: Vec<PathBuf>,
+    /// Directories polled since inotify ran out of watches, and their subdirectories
+    pub polled: Vec<PathBuf>,
 }
 
 #[inline]
-fn send_pending_rename_event(
-    rename_event: &mut Option<Event>,
+fn send_pending_rename_events(
+    rename_events: &mut VecDeque<(Instant, Event)>,
     event_handler: &mut dyn EventHandler,
 ) {
-    if let Some(e) = rename_event.take() {
+    for (_, e) in rename_events.drain(..) {
         event_handler.handle_event(Ok(e));
     }
 }
@@ -100,7 +158,12 @@ fn remove_watch_by_event(
 }
 
 impl EventLoop {
-    pub fn new(inotify: Inotify, event_handler: Box<dyn EventHandler>) -> Result<Self> {
+    pub fn new(
+        inotify: Inotify,
+        event_handler: Box<dyn EventHandler>,
+        config: Config,
+        ignore: Option<IgnoreRules>,
+    ) -> Result<Self> {
         let (event_loop_tx, event_loop_rx) = unbounded::<EventLoopMsg>();
         let poll = mio::Poll::new()?;
 
@@ -111,7 +174,7 @@ impl EventLoop {
         poll.registry()
             .register(&mut evented_inotify, INOTIFY, mio::Interest::READABLE)?;
 
-        let event_loop = EventLoop {
+        let mut event_loop = EventLoop {
             running: true,
             poll,
             event_loop_waker,
@@ -121,8 +184,34 @@ impl EventLoop {
             event_handler,
             watches: HashMap::new(),
             paths: HashMap::new(),
-            rename_event: None,
+            roots: HashMap::new(),
+            rename_events: VecDeque::new(),
+            rename_window: config.rename_window(),
+            pending_watches: config.pending_watches(),
+            pending: HashMap::new(),
+            helpers: HashMap::new(),
+            helper_paths: HashMap::new(),
+            ignore,
+            event_kinds: config.event_kinds(),
+            symlink_policy: config.symlink_policy(),
+            recovery: None,
+            fallback: None,
+            add_watch: |inotify, path, mask| inotify.add_watch(path, mask),
         };
+        if config.overflow_recovery() {
+            let (tx, rx) = unbounded();
+            event_loop.recovery = Some(Recovery {
+                data_builder: DataBuilder::new(
+                    tx,
+                    None,
+                    event_loop.ignore.clone(),
+                    None,
+                    event_loop.symlink_policy,
+                ),
+                snapshots: HashMap::new(),
+                rx,
+            });
+        }
         Ok(event_loop)
     }
 
@@ -136,8 +225,12 @@ impl EventLoop {
     fn event_loop_thread(mut self) {
         let mut events = mio::Events::with_capacity(16);
         loop {
-            // Wait for something to happen.
-            match self.poll.poll(&mut events, None) {
+            // Wait for something to happen, or for the oldest rename to expire.
+            let timeout = self
+                .rename_events
+                .front()
+                .map(|(deadline, _)| deadline.saturating_duration_since(Instant::now()));
+            match self.poll.poll(&mut events, timeout) {
                 Err(ref e) if matches!(e.kind(), std::io::ErrorKind::Interrupted) => {
                     // System call was interrupted, we will retry
                     // TODO: Not covered by tests (to reproduce likely need to setup signal handlers)
@@ -150,6 +243,7 @@ impl EventLoop {
             for event in &events {
                 self.handle_event(event);
             }
+            self.send_expired_rename_events();
 
             // Stop, if we're done.
             if !self.running {
@@ -176,11 +270,55 @@ impl EventLoop {
     fn handle_messages(&mut self) {
         while let Ok(msg) = self.event_loop_rx.try_recv() {
             match msg {
-                EventLoopMsg::AddWatch(path, recursive_mode, tx) => {
-                    let _ = tx.send(self.add_watch(path, recursive_mode.is_recursive(), true));
+                EventLoopMsg::AddWatch(path, options, tx) => {
+                    let is_recursive = options.recursive_mode().is_recursive();
+                    // the watches placed for the path subscribe to its event kinds
+                    let previous = self.roots.insert(path.clone(), options);
+                    let res = if self.pending_watches {
+                        self.add_pending_watch(path.clone(), is_recursive)
+                            .map(|_| ())
+                    } else {
+                        self.add_watch(path.clone(), is_recursive, true)
+                    };
+                    if res.is_err() {
+                        match previous {
+                            Some(previous) => self.roots.insert(path.clone(), previous),
+                            None => self.roots.remove(&path),
+                        };
+                    }
+                    if let (Ok(()), Some(recovery)) = (&res, &mut self.recovery) {
+                        recovery.data_builder.update_timestamp();
+                        let snapshot = recovery.data_builder.build_watch_data(
+                            path.clone(),
+                            is_recursive,
+                            true,
+                        );
+                        if let Some(snapshot) = snapshot {
+                            recovery.snapshots.insert(path, snapshot);
+                        }
+                        self.forward_recovery_events();
+                    }
+                    let _ = tx.send(res);
                 }
                 EventLoopMsg::RemoveWatch(path, tx) => {
-                    let _ = tx.send(self.remove_watch(path, false));
+                    self.roots.remove(&path);
+                    if let Some(ref mut recovery) = self.recovery {
+                        recovery.snapshots.remove(&path);
+                    }
+                    let res = match self.pending.remove(&path) {
+                        Some(pending) => {
+                            self.release_helper(&pending.ancestor);
+                            Ok(())
+                        }
+                        None => {
+                            let was_polled = self.stop_polling(&path);
+                            match self.remove_watch(path, false) {
+                                Err(_) if was_polled => Ok(()),
+                                res => res,
+                            }
+                        }
+                    };
+                    let _ = tx.send(res);
                 }
                 EventLoopMsg::Shutdown => {
                     let _ = self.remove_all_watches();
@@ -190,16 +328,15 @@ impl EventLoop {
                     self.running = false;
                     break;
                 }
-                EventLoopMsg::RenameTimeout(cookie) => {
-                    let current_cookie = self.rename_event.as_ref().and_then(|e| e.tracker());
-                    // send pending rename event only if the rename event for which the timer has been created hasn't been handled already; otherwise ignore this timeout
-                    if current_cookie == Some(cookie) {
-                        send_pending_rename_event(&mut self.rename_event, &mut *self.event_handler);
-                    }
-                }
                 EventLoopMsg::Configure(config, tx) => {
                     self.configure_raw_mode(config, tx);
                 }
+                EventLoopMsg::WatchedPaths(tx) => {
+                    let _ = tx.send(self.watched_paths());
+                }
+                EventLoopMsg::Watches(tx) => {
+                    let _ = tx.send(self.watch_infos());
+                }
             }
         }
     }
@@ -212,6 +349,11 @@ impl EventLoop {
     fn handle_inotify(&mut self) {
         let mut add_watches = Vec::new();
         let mut remove_watches = Vec::new();
+        let mut changed_ancestors = Vec::new();
+        let mut removed_roots = Vec::new();
+        // whether any event asked to read the whole tree below a path
+        let mut touched_paths = BTreeMap::new();
+        let mut overflowed = false;
 
         if let Some(ref mut inotify) = self.inotify {
             let mut buffer = [0; 1024];
@@ -223,64 +365,125 @@ impl EventLoop {
                         for event in events {
                             num_events += 1;
                             if event.mask.contains(EventMask::Q_OVERFLOW) {
-                                let ev = Ok(Event::new(EventKind::Other).set_flag(Flag::Rescan));
-                                self.event_handler.handle_event(ev);
+                                if self.recovery.is_some() {
+                                    overflowed = true;
+                                } else {
+                                    let ev =
+                                        Ok(Event::new(EventKind::Other).set_flag(Flag::Rescan));
+                                    self.event_handler.handle_event(ev);
+                                }
+                            }
+
+                            if let Some(ancestor) = self.helper_paths.get(&event.wd) {
+                                if event.mask.intersects(
+                                    EventMask::CREATE
+                                        | EventMask::MOVED_TO
+                                        | EventMask::DELETE_SELF
+                                        | EventMask::MOVE_SELF,
+                                ) {
+                                    changed_ancestors.push(ancestor.clone());
+                                }
+                                if !self.paths.contains_key(&event.wd) {
+                                    // only watched on behalf of pending paths
+                                    continue;
+                                }
                             }
 
                             let path = match event.name {
                                 Some(name) => {
-                                    self.paths.get(&event.wd).map(|root| root.join(&name))
+                                    self.paths.get(&event.wd).map(|root| root.join(name))
                                 }
                                 None => self.paths.get(&event.wd).cloned(),
                             };
 
+                            if self.recovery.is_some() {
+                                if let Some(ref path) = path {
+                                    // only a directory new to the tree is read as a whole
+                                    let rescan = event.mask.contains(EventMask::ISDIR)
+                                        && event
+                                            .mask
+                                            .intersects(EventMask::CREATE | EventMask::MOVED_TO);
+                                    *touched_paths.entry(path.clone()).or_insert(false) |= rescan;
+                                }
+                            }
+
+                            // changed ignore rules may uncover directories to watch
+                            if self.ignore.is_some() {
+                                if let Some(dir) =
+                                    path.as_deref().and_then(crate::gitignore::ignore_file_dir)
+                                {
+                                    if self.watches.get(dir).map_or(false, |w| w.2) {
+                                        add_watches.push(dir.to_path_buf());
+                                    }
+                                }
+                            }
+
+                            if self.pending_watches
+                                && event
+                                    .mask
+                                    .intersects(EventMask::DELETE_SELF | EventMask::MOVE_SELF)
+                            {
+                                if let Some(root) = path.as_ref().filter(|path| {
+                                    self.watches.get(*path).map_or(false, |(_, mask, _)| {
+                                        mask.contains(WatchMask::DELETE_SELF)
+                                    })
+                                }) {
+                                    removed_roots.push((root.clone(), self.watches[root].2));
+                                }
+                            }
+
                             if event.mask.contains(EventMask::MOVED_FROM) {
-                                send_pending_rename_event(
-                                    &mut self.rename_event,
-                                    &mut *self.event_handler,
-                                );
                                 remove_watch_by_event(&path, &self.watches, &mut remove_watches);
-                                self.rename_event = Some(
+                                // only subscribed to for recursive watches
+                                let event_kinds =
+                                    event_kinds_of(&self.roots, self.event_kinds, path.as_deref());
+                                if !event_kinds.contains(EventKindMask::MODIFY_NAME) {
+                                    // paths moved out are reported as removed without MODIFY_NAME
+                                    if event_kinds.contains(EventKindMask::REMOVE) {
+                                        send_pending_rename_events(
+                                            &mut self.rename_events,
+                                            &mut *self.event_handler,
+                                        );
+                                        let kind = if event.mask.contains(EventMask::ISDIR) {
+                                            RemoveKind::Folder
+                                        } else {
+                                            RemoveKind::File
+                                        };
+                                        let ev = Event::new(EventKind::Remove(kind))
+                                            .add_some_path(path.clone());
+                                        self.event_handler.handle_event(Ok(ev));
+                                    }
+                                    continue;
+                                }
+                                // The path may have been moved out of the watched directories, so
+                                // the `MOVED_TO` half is only waited for during the rename window.
+                                // After that the event is sent on its own.
+                                self.rename_events.push_back((
+                                    Instant::now() + self.rename_window,
                                     Event::new(EventKind::Modify(ModifyKind::Name(
                                         RenameMode::From,
                                     )))
                                     .add_some_path(path.clone())
                                     .set_tracker(event.cookie as usize),
-                                );
+                                ));
                             } else {
                                 let mut evs = Vec::new();
                                 if event.mask.contains(EventMask::MOVED_TO) {
-                                    if let Some(e) = self.rename_event.take() {
-                                        if e.tracker() == Some(event.cookie as usize) {
-                                            self.event_handler.handle_event(Ok(e.clone()));
-                                            evs.push(
-                                                Event::new(EventKind::Modify(ModifyKind::Name(
-                                                    RenameMode::To,
-                                                )))
-                                                .set_tracker(event.cookie as usize)
-                                                .add_some_path(path.clone()),
-                                            );
-                                            evs.push(
-                                                Event::new(EventKind::Modify(ModifyKind::Name(
-                                                    RenameMode::Both,
-                                                )))
-                                                .set_tracker(event.cookie as usize)
-                                                .add_some_path(e.paths.first().cloned())
-                                                .add_some_path(path.clone()),
-                                            );
-                                        } else {
-                                            // TODO should it be rename?
-                                            evs.push(
-                                                Event::new(EventKind::Create(
-                                                    if event.mask.contains(EventMask::ISDIR) {
-                                                        CreateKind::Folder
-                                                    } else {
-                                                        CreateKind::File
-                                                    },
-                                                ))
-                                                .add_some_path(path.clone()),
-                                            );
-                                        }
+                                    let cookie = Some(event.cookie as usize);
+                                    let from = self
+                                        .rename_events
+                                        .iter()
+                                        .position(|(_, e)| e.tracker() == cookie)
+                                        .and_then(|i| self.rename_events.remove(i));
+                                    if let Some((_, e)) = from {
+                                        evs.push(
+                                            Event::new(EventKind::Modify(ModifyKind::Name(
+                                                RenameMode::Both,
+                                            )))
+                                            .set_tracker(event.cookie as usize)
+                                            .add_some_path(e.paths.first().cloned())
+                                            .add_some_path(path.clone()),
+                                        );
                                     } else {
                                         // TODO should it be rename?
                                         evs.push(
@@ -389,10 +592,21 @@ impl EventLoop {
                                         .add_some_path(path.clone()),
                                     );
                                 }
+                                if event.mask.contains(EventMask::ACCESS) {
+                                    evs.push(
+                                        Event::new(EventKind::Access(AccessKind::Read))
+                                            .add_some_path(path.clone()),
+                                    );
+                                }
+
+                                // recursive watches subscribe to more than asked for
+                                let event_kinds =
+                                    event_kinds_of(&self.roots, self.event_kinds, path.as_deref());
+                                evs.retain(|ev| event_kinds.matches(&ev.kind));
 
                                 if !evs.is_empty() {
-                                    send_pending_rename_event(
-                                        &mut self.rename_event,
+                                    send_pending_rename_events(
+                                        &mut self.rename_events,
                                         &mut *self.event_handler,
                                     );
                                 }
@@ -407,30 +621,6 @@ impl EventLoop {
                         if num_events == 0 {
                             break;
                         }
-
-                        // When receiving only the first part of a move event (IN_MOVED_FROM) it is unclear
-                        // whether the second part (IN_MOVED_TO) will arrive because the file or directory
-                        // could just have been moved out of the watched directory. So it's necessary to wait
-                        // for possible subsequent events in case it's a complete move event but also to make sure
-                        // that the first part of the event is handled in a timely manner in case no subsequent events arrive.
-                        // TODO: don't do this here, instead leave it entirely to the debounce
-                        // -> related to some rename events being reported as creates.
-
-                        if let Some(ref rename_event) = self.rename_event {
-                            let event_loop_tx = self.event_loop_tx.clone();
-                            let waker = self.event_loop_waker.clone();
-                            let cookie = rename_event.tracker().unwrap(); // unwrap is safe because rename_event is always set with some cookie
-                            let _ = thread::Builder::new()
-                                .name("notify-rs inotify rename".to_string())
-                                .spawn(move || {
-                                    thread::sleep(Duration::from_millis(10)); // wait up to 10 ms for a subsequent event
-
-                                    // An error here means the other end of the channel was closed, a thing that can
-                                    // happen normally.
-                                    let _ = event_loop_tx.send(EventLoopMsg::RenameTimeout(cookie));
-                                    let _ = waker.wake();
-                                });
-                        }
                     }
                     Err(e) => {
                         self.event_handler.handle_event(Err(Error::io(e)));
@@ -444,7 +634,222 @@ impl EventLoop {
         }
 
         for path in add_watches {
-            self.add_watch(path, true, false).ok();
+            if !self.is_ignored(&path) {
+                self.add_watch(path, true, false).ok();
+            }
+        }
+
+        // watched paths that went away wait for their return
+        for (path, is_recursive) in removed_roots {
+            self.remove_watch(path.clone(), true).ok();
+            // it may be back already
+            self.resolve_pending_watch(path, is_recursive);
+        }
+
+        // pending paths, or one of their ancestors, may have been created
+        changed_ancestors.dedup();
+        for ancestor in changed_ancestors {
+            let targets: Vec<_> = self
+                .pending
+                .iter()
+                .filter(|(_, pending)| pending.ancestor == ancestor)
+                .map(|(path, pending)| (path.clone(), pending.is_recursive))
+                .collect();
+            for (path, is_recursive) in targets {
+                self.resolve_pending_watch(path, is_recursive);
+            }
+        }
+
+        if let Some(ref mut recovery) = self.recovery {
+            for (path, rescan) in touched_paths {
+                for snapshot in recovery.snapshots.values_mut() {
+                    snapshot.refresh(&recovery.data_builder, &path, rescan);
+                }
+            }
+            // errors reading the touched paths
+            self.forward_recovery_events();
+        }
+        if overflowed {
+            self.recover_from_overflow();
+        }
+    }
+
+    /// Catch up on the events lost in a queue overflow, see [`Config::with_overflow_recovery`]
+    fn recover_from_overflow(&mut self) {
+        let roots: Vec<_> = match self.recovery {
+            Some(ref recovery) => recovery
+                .snapshots
+                .iter()
+                .map(|(root, snapshot)| (root.clone(), snapshot.is_recursive()))
+                .collect(),
+            None => return,
+        };
+
+        // watches of removed directories are gone, while new directories lack one
+        let stale: Vec<_> = self
+            .watches
+            .keys()
+            .filter(|path| !path.exists())
+            .cloned()
+            .collect();
+        for path in stale {
+            self.remove_watch(path, false).ok();
+        }
+        for (root, is_recursive) in roots {
+            if !self.watches.contains_key(&root) && self.pending_watches {
+                self.resolve_pending_watch(root, is_recursive);
+            } else if root.exists() {
+                self.add_watch(root, is_recursive, true).ok();
+            }
+        }
+
+        if let Some(ref mut recovery) = self.recovery {
+            recovery.data_builder.update_timestamp();
+            for snapshot in recovery.snapshots.values_mut() {
+                snapshot.rescan(&mut recovery.data_builder);
+            }
+        }
+        self.forward_recovery_events();
+    }
+
+    /// Pass on the events and errors of the recovery snapshots, of the kinds the watches ask for
+    fn forward_recovery_events(&mut self) {
+        let recovery = match self.recovery {
+            Some(ref recovery) => recovery,
+            None => return,
+        };
+        for event in recovery.rx.try_iter() {
+            match event {
+                Ok(ref e)
+                    if !event_kinds_of(
+                        &self.roots,
+                        self.event_kinds,
+                        e.paths.first().map(PathBuf::as_path),
+                    )
+                    .matches(&e.kind) => {}
+                event => self.event_handler.handle_event(event),
+            }
+        }
+    }
+
+    /// Try to activate a pending watch, reporting the creation of its path if it succeeds
+    fn resolve_pending_watch(&mut self, path: PathBuf, is_recursive: bool) {
+        match self.add_pending_watch(path.clone(), is_recursive) {
+            Ok(true) => {
+                // the creation was not reported if the parent isn't watched
+                let parent_watched = path
+                    .parent()
+                    .map_or(false, |parent| self.watches.contains_key(parent));
+                let event_kinds = event_kinds_of(&self.roots, self.event_kinds, Some(&path));
+                if !parent_watched && event_kinds.contains(EventKindMask::CREATE) {
+                    let kind = if path.is_dir() {
+                        CreateKind::Folder
+                    } else {
+                        CreateKind::File
+                    };
+                    let ev = Event::new(EventKind::Create(kind)).add_path(path);
+                    self.event_handler.handle_event(Ok(ev));
+                }
+            }
+            Ok(false) => {}
+            Err(e) => self.event_handler.handle_event(Err(e)),
+        }
+    }
+
+    /// Send the `MOVED_FROM` events whose `MOVED_TO` didn't arrive in time
+    fn send_expired_rename_events(&mut self) {
+        let now = Instant::now();
+        while let Some((deadline, _)) = self.rename_events.front() {
+            if *deadline > now {
+                break;
+            }
+            if let Some((_, e)) = self.rename_events.pop_front() {
+                self.event_handler.handle_event(Ok(e));
+            }
+        }
+    }
+
+    /// Watch `path`, or keep it pending until it exists.
+    ///
+    /// Returns whether the watch is active.
+    fn add_pending_watch(&mut self, path: PathBuf, is_recursive: bool) -> Result<bool> {
+        loop {
+            if let Some(pending) = self.pending.remove(&path) {
+                self.release_helper(&pending.ancestor);
+            }
+
+            match self.add_watch(path.clone(), is_recursive, true) {
+                Ok(()) => return Ok(true),
+                Err(e) if is_not_found(&e) => {}
+                Err(e) => return Err(e),
+            }
+
+            let ancestor = match nearest_existing_ancestor(&path) {
+                Some(ancestor) => ancestor,
+                None => return Err(Error::path_not_found().add_path(path)),
+            };
+            match self.add_helper(&ancestor) {
+                Ok(()) => {}
+                // gone again, look further up
+                Err(e) if is_not_found(&e) => continue,
+                Err(e) => return Err(e),
+            }
+            self.pending.insert(
+                path.clone(),
+                PendingWatch {
+                    is_recursive,
+                    ancestor: ancestor.clone(),
+                },
+            );
+
+            // the path may have been created before the helper watch was in place
+            if !path.exists() && nearest_existing_ancestor(&path).as_ref() == Some(&ancestor) {
+                return Ok(false);
+            }
+        }
+    }
+
+    fn add_helper(&mut self, ancestor: &Path) -> Result<()> {
+        if let Some((_, users)) = self.helpers.get_mut(ancestor) {
+            *users += 1;
+            return Ok(());
+        }
+
+        if let Some(ref mut inotify) = self.inotify {
+            // MASK_ADD to not clobber a regular watch of the same directory
+            let mask = WatchMask::CREATE
+                | WatchMask::MOVED_TO
+                | WatchMask::DELETE_SELF
+                | WatchMask::MOVE_SELF
+                | WatchMask::ONLYDIR
+                | WatchMask::MASK_ADD;
+            let w = inotify
+                .add_watch(ancestor, mask)
+                .map_err(|e| Error::io(e).add_path(ancestor.to_path_buf()))?;
+            self.helper_paths.insert(w.clone(), ancestor.to_path_buf());
+            self.helpers.insert(ancestor.to_path_buf(), (w, 1));
+        }
+        Ok(())
+    }
+
+    fn release_helper(&mut self, ancestor: &Path) {
+        let unused = match self.helpers.get_mut(ancestor) {
+            Some((_, users)) => {
+                *users -= 1;
+                *users == 0
+            }
+            None => false,
+        };
+        if unused {
+            if let Some((w, _)) = self.helpers.remove(ancestor) {
+                self.helper_paths.remove(&w);
+                // keep the kernel watch if the directory is watched regularly as well
+                if !self.paths.contains_key(&w) {
+                    if let Some(ref mut inotify) = self.inotify {
+                        let _ = inotify.rm_watch(w);
+                    }
+                }
+            }
         }
     }
 
@@ -452,34 +857,195 @@ impl EventLoop {
         // If the watch is not recursive, or if we determine (by stat'ing the path to get its
         // metadata) that the watched path is not a directory, add a single path watch.
         if !is_recursive || !metadata(&path).map_err(Error::io)?.is_dir() {
-            return self.add_single_watch(path, false, true);
+            return match self.add_single_watch(path.clone(), false, true) {
+                Err(ref e) if self.can_poll(e) => self.start_polling(path, false),
+                res => res,
+            };
         }
 
-        for entry in WalkDir::new(path)
-            .follow_links(true)
+        let ignore = self.ignore.clone();
+        // links pointing out of the watched root are left out
+        let canonical_root = match self.symlink_policy {
+            SymlinkPolicy::FollowWithinRoot => fs::canonicalize(self.watch_root(&path)).ok(),
+            _ => None,
+        };
+        // following links, walkdir stops at directories containing themselves
+        let mut entries = WalkDir::new(path)
+            .follow_links(self.symlink_policy != SymlinkPolicy::DontFollow)
             .into_iter()
-            .filter_map(filter_dir)
-        {
-            self.add_single_watch(entry.path().to_path_buf(), is_recursive, watch_self)?;
+            .filter_entry(|entry| {
+                if entry.depth() == 0 {
+                    return true;
+                }
+                if let Some(ref ignore) = ignore {
+                    if ignore.is_ignored(entry.path()) {
+                        return false;
+                    }
+                }
+                match canonical_root {
+                    Some(ref root) if entry.path_is_symlink() => fs::canonicalize(entry.path())
+                        .map_or(false, |target| target.starts_with(root)),
+                    _ => true,
+                }
+            });
+        while let Some(entry) = entries.next() {
+            let path = match filter_dir(entry) {
+                Some(entry) => entry.into_path(),
+                None => continue,
+            };
+            if self.is_polled(&path) {
+                entries.skip_current_dir();
+                continue;
+            }
+            match self.add_single_watch(path.clone(), is_recursive, watch_self) {
+                Err(ref e) if self.can_poll(e) => {
+                    // the rest of the subtree is polled
+                    self.start_polling(path, true)?;
+                    entries.skip_current_dir();
+                }
+                res => res?,
+            }
             watch_self = false;
         }
 
         Ok(())
     }
 
+    /// The watched root `path` is below, or `path` itself if it is one
+    fn watch_root<'a>(&self, path: &'a Path) -> &'a Path {
+        path.ancestors()
+            .find(|p| {
+                self.watches
+                    .get(*p)
+                    .map_or(false, |(_, mask, _)| mask.contains(WatchMask::DELETE_SELF))
+            })
+            .unwrap_or(path)
+    }
+
+    /// Whether `e` is the watch limit being hit, with polling to fall back to
+    fn can_poll(&self, e: &Error) -> bool {
+        self.fallback.is_some() && matches!(e.kind, ErrorKind::MaxFilesWatch)
+    }
+
+    fn is_polled(&self, path: &Path) -> bool {
+        self.fallback
+            .as_ref()
+            .map_or(false, |fallback| fallback.polled.contains_key(path))
+    }
+
+    fn start_polling(&mut self, path: PathBuf, is_recursive: bool) -> Result<()> {
+        // polled with the event kinds of the watch, if it has any
+        let event_kinds = nearest_watch(&self.roots, &path).and_then(WatchOptions::event_kinds);
+        let fallback = match self.fallback {
+            Some(ref mut fallback) => fallback,
+            None => return Ok(()),
+        };
+        let recursive_mode = if is_recursive {
+            RecursiveMode::Recursive
+        } else {
+            RecursiveMode::NonRecursive
+        };
+        let options = WatchOptions::new(recursive_mode);
+        let options = match event_kinds {
+            Some(event_kinds) => options.with_event_kinds(event_kinds),
+            None => options,
+        };
+        // the poll thread scans the tree, not to stall the delivery of inotify events
+        fallback.watcher.watch_deferred(&path, options)?;
+        fallback.polled.insert(path, is_recursive);
+        Ok(())
+    }
+
+    /// Stop polling `path` and the directories below it.
+    ///
+    /// Returns whether `path` itself was polled.
+    fn stop_polling(&mut self, path: &Path) -> bool {
+        let fallback = match self.fallback {
+            Some(ref mut fallback) => fallback,
+            None => return false,
+        };
+        let was_polled = fallback.polled.contains_key(path);
+        let watcher = &mut fallback.watcher;
+        fallback.polled.retain(|polled, _| {
+            if !polled.starts_with(path) {
+                return true;
+            }
+            let _ = watcher.unwatch(polled);
+            false
+        });
+        was_polled
+    }
+
+    fn watched_paths(&self) -> WatchedPaths {
+        let mut native: Vec<_> = self.watches.keys().cloned().collect();
+        native.sort();
+        let mut polled: Vec<_> = self
+            .fallback
+            .iter()
+            .flat_map(|fallback| fallback.polled.keys().cloned())
+            .collect();
+        polled.sort();
+        WatchedPaths { native, polled }
+    }
+
+    fn watch_infos(&self) -> Vec<WatchInfo> {
+        let polled: Vec<_> = self
+            .fallback
+            .iter()
+            .flat_map(|fallback| fallback.polled.keys())
+            .collect();
+        let mut infos: Vec<_> = self
+            .roots
+            .iter()
+            .map(|(root, options)| {
+                let is_recursive = options.recursive_mode().is_recursive();
+                let below = |path: &Path| {
+                    if is_recursive {
+                        path.starts_with(root)
+                    } else {
+                        path == root
+                    }
+                };
+                let status = if self.pending.contains_key(root) {
+                    WatchStatus::Pending
+                } else if polled.iter().any(|path| below(path)) {
+                    WatchStatus::Polled
+                } else if self.watches.contains_key(root) {
+                    WatchStatus::Active
+                } else {
+                    WatchStatus::Removed
+                };
+                WatchInfo {
+                    path: root.clone(),
+                    recursive_mode: options.recursive_mode(),
+                    count: self.watches.keys().filter(|path| below(path)).count(),
+                    status,
+                }
+            })
+            .collect();
+        infos.sort_by(|a, b| a.path.cmp(&b.path));
+        infos
+    }
+
+    fn is_ignored(&self, path: &Path) -> bool {
+        self.ignore
+            .as_ref()
+            .map_or(false, |ignore| ignore.is_ignored(path))
+    }
+
     fn add_single_watch(
         &mut self,
         path: PathBuf,
         is_recursive: bool,
         watch_self: bool,
     ) -> Result<()> {
-        let mut watchmask = WatchMask::ATTRIB
-            | WatchMask::CREATE
-            | WatchMask::DELETE
-            | WatchMask::CLOSE_WRITE
-            | WatchMask::MODIFY
-            | WatchMask::MOVED_FROM
-            | WatchMask::MOVED_TO;
+        let mut watchmask = watch_mask(event_kinds_of(&self.roots, self.event_kinds, Some(&path)));
+        if is_recursive {
+            // needed to follow the directory tree
+            watchmask.insert(
+                WatchMask::CREATE | WatchMask::DELETE | WatchMask::MOVED_FROM | WatchMask::MOVED_TO,
+            );
+        }
 
         if watch_self {
             watchmask.insert(WatchMask::DELETE_SELF);
@@ -489,10 +1055,12 @@ impl EventLoop {
         if let Some(&(_, old_watchmask, _)) = self.watches.get(&path) {
             watchmask.insert(old_watchmask);
             watchmask.insert(WatchMask::MASK_ADD);
+        } else if self.helpers.contains_key(&path) {
+            watchmask.insert(WatchMask::MASK_ADD);
         }
 
         if let Some(ref mut inotify) = self.inotify {
-            match inotify.add_watch(&path, watchmask) {
+            match (self.add_watch)(inotify, &path, watchmask) {
                 Err(e) => {
                     Err(if e.raw_os_error() == Some(libc::ENOSPC) {
                         // do not report inotify limits as "no more space" on linux #266
@@ -520,9 +1088,12 @@ impl EventLoop {
             None => return Err(Error::watch_not_found().add_path(path)),
             Some((w, _, is_recursive)) => {
                 if let Some(ref mut inotify) = self.inotify {
-                    inotify
-                        .rm_watch(w.clone())
-                        .map_err(|e| Error::io(e).add_path(path.clone()))?;
+                    // still needed for a pending path
+                    if !self.helper_paths.contains_key(&w) {
+                        inotify
+                            .rm_watch(w.clone())
+                            .map_err(|e| Error::io(e).add_path(path.clone()))?;
+                    }
                     self.paths.remove(&w);
 
                     if is_recursive || remove_recursive {
@@ -553,16 +1124,89 @@ impl EventLoop {
                     .rm_watch(w.clone())
                     .map_err(|e| Error::io(e).add_path(p.into()))?;
             }
+            for (w, p) in &self.helper_paths {
+                if !self.paths.contains_key(w) {
+                    inotify
+                        .rm_watch(w.clone())
+                        .map_err(|e| Error::io(e).add_path(p.into()))?;
+                }
+            }
             self.watches.clear();
             self.paths.clear();
+            self.pending.clear();
+            self.helpers.clear();
+            self.helper_paths.clear();
+        }
+        if let Some(ref mut fallback) = self.fallback {
+            for path in fallback.polled.keys() {
+                let _ = fallback.watcher.unwatch(path);
+            }
+            fallback.polled.clear();
         }
         Ok(())
     }
 }
 
+/// The classes of events to deliver for `path`, by the nearest watched path above it
+fn event_kinds_of(
+    roots: &HashMap<PathBuf, WatchOptions>,
+    default: EventKindMask,
+    path: Option<&Path>,
+) -> EventKindMask {
+    path.and_then(|path| nearest_watch(roots, path))
+        .and_then(WatchOptions::event_kinds)
+        .unwrap_or(default)
+}
+
+/// The inotify events making up `event_kinds`
+fn watch_mask(event_kinds: EventKindMask) -> WatchMask {
+    let classes = [
+        // paths moved in are reported as created without MODIFY_NAME
+        (
+            EventKindMask::CREATE,
+            WatchMask::CREATE | WatchMask::MOVED_TO,
+        ),
+        // and paths moved out as removed
+        (
+            EventKindMask::REMOVE,
+            WatchMask::DELETE | WatchMask::MOVED_FROM,
+        ),
+        (EventKindMask::MODIFY_DATA, WatchMask::MODIFY),
+        (EventKindMask::MODIFY_META, WatchMask::ATTRIB),
+        (
+            EventKindMask::MODIFY_NAME,
+            WatchMask::MOVED_FROM | WatchMask::MOVED_TO,
+        ),
+        (EventKindMask::ACCESS_READ, WatchMask::ACCESS),
+        (EventKindMask::ACCESS_OPEN, WatchMask::OPEN),
+        (EventKindMask::ACCESS_CLOSE_WRITE, WatchMask::CLOSE_WRITE),
+        (EventKindMask::ACCESS_CLOSE_READ, WatchMask::CLOSE_NOWRITE),
+    ];
+    classes
+        .iter()
+        .filter(|(class, _)| event_kinds.contains(*class))
+        .fold(WatchMask::empty(), |mask, (_, events)| mask | *events)
+}
+
+fn is_not_found(e: &Error) -> bool {
+    matches!(e.kind, ErrorKind::Io(ref e) if e.kind() == io::ErrorKind::NotFound)
+}
+
+/// The nearest ancestor of `path` that is an existing directory
+fn nearest_existing_ancestor(path: &Path) -> Option<PathBuf> {
+    path.ancestors()
+        .skip(1)
+        .find(|ancestor| ancestor.is_dir())
+        .map(Path::to_path_buf)
+}
+
 /// return `DirEntry` when it is a directory
 fn filter_dir(e: walkdir::Result<walkdir::DirEntry>) -> Option<walkdir::DirEntry> {
     if let Ok(e) = e {
+        // the root is known to be a directory, even when links below it are not followed
+        if e.depth() == 0 {
+            return Some(e);
+        }
         if let Ok(metadata) = e.metadata() {
             if metadata.is_dir() {
                 return Some(e);
@@ -573,29 +1217,67 @@ fn filter_dir(e: walkdir::Result<walkdir::DirEntry>) -> Option<walkdir::DirEntry
 }
 
 impl INotifyWatcher {
-    fn from_event_handler(event_handler: Box<dyn EventHandler>) -> Result<Self> {
+    fn from_event_handler(event_handler: Box<dyn EventHandler>, config: Config) -> Result<Self> {
+        let roots = WatchFilters::default();
+        let (event_handler, ignore) =
+            crate::gitignore::wrap_handler(event_handler, &config, &roots);
         let inotify = Inotify::init()?;
-        let event_loop = EventLoop::new(inotify, event_handler)?;
+        let event_loop = EventLoop::new(inotify, event_handler, config, ignore)?;
+        Ok(Self::from_event_loop(event_loop, roots))
+    }
+
+    /// Watcher polling with `fallback` where inotify runs out of watches
+    pub(crate) fn with_fallback(
+        event_handler: Box<dyn EventHandler>,
+        config: Config,
+        fallback: PollWatcher,
+    ) -> Result<Self> {
+        let roots = WatchFilters::default();
+        let (event_handler, ignore) =
+            crate::gitignore::wrap_handler(event_handler, &config, &roots);
+        let inotify = Inotify::init()?;
+        let mut event_loop = EventLoop::new(inotify, event_handler, config, ignore)?;
+        event_loop.fallback = Some(Fallback {
+            watcher: fallback,
+            polled: HashMap::new(),
+        });
+        Ok(Self::from_event_loop(event_loop, roots))
+    }
+
+    fn from_event_loop(event_loop: EventLoop, roots: WatchFilters) -> Self {
         let channel = event_loop.event_loop_tx.clone();
         let waker = event_loop.event_loop_waker.clone();
         event_loop.run();
-        Ok(INotifyWatcher { channel, waker })
+        INotifyWatcher {
+            channel,
+            waker,
+            roots,
+        }
+    }
+
+    pub(crate) fn watched_paths(&self) -> Result<WatchedPaths> {
+        let (tx, rx) = unbounded();
+        self.channel.send(EventLoopMsg::WatchedPaths(tx))?;
+        self.waker.wake()?;
+        Ok(rx.recv()?)
     }
 
-    fn watch_inner(&mut self, path: &Path, recursive_mode: RecursiveMode) -> Result<()> {
+    fn watch_inner(&mut self, path: &Path, options: WatchOptions) -> Result<()> {
         let pb = if path.is_absolute() {
             path.to_owned()
         } else {
             let p = env::current_dir().map_err(Error::io)?;
             p.join(path)
         };
-        let (tx, rx) = unbounded();
-        let msg = EventLoopMsg::AddWatch(pb, recursive_mode, tx);
+        self.roots.add(pb.clone(), options, || {
+            let (tx, rx) = unbounded();
+            let msg = EventLoopMsg::AddWatch(pb, options, tx);
 
-        // we expect the event loop to live and reply => unwraps must not panic
-        self.channel.send(msg).unwrap();
-        self.waker.wake().unwrap();
-        rx.recv().unwrap()
+            // we expect the event loop to live and reply => unwraps must not panic
+            self.channel.send(msg).unwrap();
+            self.waker.wake().unwrap();
+            rx.recv().unwrap()
+        })
     }
 
     fn unwatch_inner(&mut self, path: &Path) -> Result<()> {
@@ -606,23 +1288,29 @@ impl INotifyWatcher {
             p.join(path)
         };
         let (tx, rx) = unbounded();
-        let msg = EventLoopMsg::RemoveWatch(pb, tx);
+        let msg = EventLoopMsg::RemoveWatch(pb.clone(), tx);
 
         // we expect the event loop to live and reply => unwraps must not panic
         self.channel.send(msg).unwrap();
         self.waker.wake().unwrap();
-        rx.recv().unwrap()
+        rx.recv().unwrap()?;
+        self.roots.remove(&pb);
+        Ok(())
     }
 }
 
 impl Watcher for INotifyWatcher {
     /// Create a new watcher.
-    fn new<F: EventHandler>(event_handler: F, _config: Config) -> Result<Self> {
-        Self::from_event_handler(Box::new(event_handler))
+    fn new<F: EventHandler>(event_handler: F, config: Config) -> Result<Self> {
+        Self::from_event_handler(Box::new(event_handler), config)
     }
 
     fn watch(&mut self, path: &Path, recursive_mode: RecursiveMode) -> Result<()> {
-        self.watch_inner(path, recursive_mode)
+        self.watch_inner(path, WatchOptions::new(recursive_mode))
+    }
+
+    fn watch_with(&mut self, path: &Path, options: WatchOptions) -> Result<()> {
+        self.watch_inner(path, options)
     }
 
     fn unwatch(&mut self, path: &Path) -> Result<()> {
@@ -636,6 +1324,13 @@ impl Watcher for INotifyWatcher {
         rx.recv()?
     }
 
+    fn watches(&self) -> Result<Vec<WatchInfo>> {
+        let (tx, rx) = unbounded();
+        self.channel.send(EventLoopMsg::Watches(tx))?;
+        self.waker.wake()?;
+        Ok(rx.recv()?)
+    }
+
     fn kind() -> crate::WatcherKind {
         crate::WatcherKind::Inotify
     }
@@ -654,3 +1349,416 @@ fn inotify_watcher_is_send_and_sync() {
     fn check<T: Send + Sync>() {}
     check::<INotifyWatcher>();
 }
+
+#[test]
+fn inotify_watches_pending_paths() {
+    use std::time::Duration;
+
+    let dir = tempfile::tempdir().unwrap();
+    let target = dir.path().join("a").join("b");
+
+    let (tx, rx) = std::sync::mpsc::channel();
+    let mut watcher =
+        INotifyWatcher::new(tx, Config::default().with_pending_watches(true)).unwrap();
+    watcher.watch(&target, RecursiveMode::Recursive).unwrap();
+
+    let wait_for_create = || loop {
+        let event = rx.recv_timeout(Duration::from_secs(5)).unwrap().unwrap();
+        if event.kind.is_create() && event.paths == [target.clone()] {
+            break;
+        }
+    };
+
+    std::fs::create_dir_all(&target).unwrap();
+    wait_for_create();
+
+    std::fs::remove_dir(&target).unwrap();
+    std::fs::create_dir(&target).unwrap();
+    wait_for_create();
+}
+
+#[cfg(feature = "gitignore")]
+#[test]
+fn inotify_skips_gitignored_paths() {
+    use std::{fs, time::Duration};
+
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    fs::create_dir_all(root.join(".git")).unwrap();
+    fs::create_dir_all(root.join("target")).unwrap();
+    fs::write(root.join(".gitignore"), "target/\n").unwrap();
+
+    let (tx, rx) = std::sync::mpsc::channel();
+    let mut watcher = INotifyWatcher::new(tx, Config::default().with_gitignore(true)).unwrap();
+    watcher.watch(root, RecursiveMode::Recursive).unwrap();
+
+    let next_paths = || {
+        rx.recv_timeout(Duration::from_secs(5))
+            .unwrap()
+            .unwrap()
+            .paths
+    };
+
+    fs::write(root.join("target").join("a"), "").unwrap();
+    fs::write(root.join("kept"), "").unwrap();
+    assert_eq!(next_paths(), [root.join("kept")]);
+    while rx.recv_timeout(Duration::from_millis(100)).is_ok() {}
+
+    // reloaded rules watch the directory from now on
+    fs::write(root.join(".gitignore"), "").unwrap();
+    assert_eq!(next_paths(), [root.join(".gitignore")]);
+    while rx.recv_timeout(Duration::from_millis(100)).is_ok() {}
+    fs::write(root.join("target").join("b"), "").unwrap();
+    assert_eq!(next_paths(), [root.join("target").join("b")]);
+}
+
+#[test]
+fn inotify_subscribes_to_configured_event_kinds() {
+    use std::{fs, time::Duration};
+
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("file");
+    fs::write(&file, "content").unwrap();
+
+    let (tx, rx) = std::sync::mpsc::channel();
+    let event_kinds = EventKindMask::ACCESS_READ | EventKindMask::ACCESS_CLOSE_WRITE;
+    let config = Config::default().with_event_kinds(event_kinds);
+    let mut watcher = INotifyWatcher::new(tx, config).unwrap();
+    watcher.watch(dir.path(), RecursiveMode::Recursive).unwrap();
+
+    fs::read(&file).unwrap();
+    fs::write(&file, "changed").unwrap();
+
+    let mut kinds = Vec::new();
+    while let Ok(event) = rx.recv_timeout(Duration::from_millis(200)) {
+        kinds.push(event.unwrap().kind);
+    }
+    // reading the whole file may take several reads
+    kinds.dedup();
+    assert_eq!(
+        kinds,
+        [
+            EventKind::Access(AccessKind::Read),
+            EventKind::Access(AccessKind::Close(AccessMode::Write)),
+        ]
+    );
+}
+
+#[test]
+fn inotify_filters_event_kinds_per_watch() {
+    use std::{fs, time::Duration};
+
+    let dir = tempfile::tempdir().unwrap();
+    let logs = dir.path().join("logs");
+    let other = dir.path().join("other");
+    fs::create_dir(&logs).unwrap();
+    fs::create_dir(&other).unwrap();
+    fs::write(logs.join("old"), "").unwrap();
+
+    let (tx, rx) = std::sync::mpsc::channel();
+    let mut watcher = INotifyWatcher::new(tx, Config::default()).unwrap();
+    let options = WatchOptions::new(RecursiveMode::NonRecursive)
+        .with_event_kinds(EventKindMask::CREATE | EventKindMask::REMOVE);
+    watcher.watch_with(&logs, options).unwrap();
+    watcher.watch(&other, RecursiveMode::NonRecursive).unwrap();
+
+    fs::write(logs.join("old"), "changed").unwrap();
+    fs::write(logs.join("new"), "").unwrap();
+    fs::write(other.join("file"), "").unwrap();
+
+    let mut events = Vec::new();
+    while let Ok(event) = rx.recv_timeout(Duration::from_millis(200)) {
+        let event = event.unwrap();
+        events.push((event.paths, event.kind));
+    }
+    assert_eq!(
+        events,
+        [
+            (vec![logs.join("new")], EventKind::Create(CreateKind::File)),
+            (
+                vec![other.join("file")],
+                EventKind::Create(CreateKind::File)
+            ),
+            (
+                vec![other.join("file")],
+                EventKind::Access(AccessKind::Close(AccessMode::Write))
+            ),
+        ]
+    );
+}
+
+#[test]
+fn inotify_reports_moves_without_renames_as_create_and_remove() {
+    use std::{fs, time::Duration};
+
+    let dir = tempfile::tempdir().unwrap();
+    let watched = dir.path().join("watched");
+    fs::create_dir(&watched).unwrap();
+    fs::write(watched.join("a"), "").unwrap();
+    fs::write(dir.path().join("b"), "").unwrap();
+
+    let (tx, rx) = std::sync::mpsc::channel();
+    let mut watcher = INotifyWatcher::new(tx, Config::default()).unwrap();
+    let options = WatchOptions::new(RecursiveMode::NonRecursive)
+        .with_event_kinds(EventKindMask::CREATE | EventKindMask::REMOVE);
+    watcher.watch_with(&watched, options).unwrap();
+
+    fs::rename(watched.join("a"), dir.path().join("a")).unwrap();
+    fs::rename(dir.path().join("b"), watched.join("b")).unwrap();
+
+    let mut events = Vec::new();
+    while let Ok(event) = rx.recv_timeout(Duration::from_millis(200)) {
+        let event = event.unwrap();
+        events.push((event.kind, event.paths));
+    }
+    assert_eq!(
+        events,
+        [
+            (EventKind::Remove(RemoveKind::File), vec![watched.join("a")]),
+            (EventKind::Create(CreateKind::File), vec![watched.join("b")]),
+        ]
+    );
+}
+
+#[test]
+fn inotify_pairs_renames_into_one_event() {
+    use std::{fs, time::Duration};
+
+    let dir = tempfile::tempdir().unwrap();
+    let watched = dir.path().join("watched");
+    fs::create_dir(&watched).unwrap();
+    fs::write(watched.join("a"), "").unwrap();
+    fs::write(watched.join("c"), "").unwrap();
+
+    let (tx, rx) = std::sync::mpsc::channel();
+    let config = Config::default()
+        .with_event_kinds(EventKindMask::MODIFY_NAME)
+        .with_rename_window(Duration::from_millis(50));
+    let mut watcher = INotifyWatcher::new(tx, config).unwrap();
+    watcher.watch(&watched, RecursiveMode::Recursive).unwrap();
+
+    fs::rename(watched.join("a"), watched.join("b")).unwrap();
+    fs::rename(watched.join("c"), dir.path().join("c")).unwrap();
+
+    let mut events = Vec::new();
+    while let Ok(event) = rx.recv_timeout(Duration::from_millis(300)) {
+        let event = event.unwrap();
+        events.push((event.kind, event.paths));
+    }
+    assert_eq!(
+        events,
+        [
+            (
+                EventKind::Modify(ModifyKind::Name(RenameMode::Both)),
+                vec![watched.join("a"), watched.join("b")]
+            ),
+            (
+                EventKind::Modify(ModifyKind::Name(RenameMode::From)),
+                vec![watched.join("c")]
+            ),
+        ]
+    );
+}
+
+#[test]
+fn inotify_recovers_from_queue_overflow() {
+    use std::{collections::HashSet, fs, sync::mpsc, time::Duration};
+
+    let dir = tempfile::tempdir().unwrap();
+    let (tx, rx) = mpsc::channel();
+    // holds up the event loop until the kernel queue overflowed
+    let (gate_tx, gate_rx) = mpsc::channel::<()>();
+    let mut gate = Some(gate_rx);
+    let handler = move |event: Result<Event>| {
+        if let Some(gate) = gate.take() {
+            let _ = gate.recv();
+        }
+        let _ = tx.send(event);
+    };
+
+    let config = Config::default().with_overflow_recovery(true);
+    let mut watcher = INotifyWatcher::new(handler, config).unwrap();
+    watcher.watch(dir.path(), RecursiveMode::Recursive).unwrap();
+
+    fs::write(dir.path().join("first"), "").unwrap();
+    std::thread::sleep(Duration::from_millis(100));
+    // more events than the default limit of 16384 queued events
+    let mut files: HashSet<_> = (0..9000).map(|i| dir.path().join(i.to_string())).collect();
+    for file in &files {
+        fs::write(file, "x").unwrap();
+    }
+    // lost in the overflow, so only the recovery places its watch
+    fs::create_dir(dir.path().join("sub")).unwrap();
+    fs::write(dir.path().join("sub").join("file"), "").unwrap();
+    files.insert(dir.path().join("sub"));
+    files.insert(dir.path().join("sub").join("file"));
+    gate_tx.send(()).unwrap();
+
+    let mut created = HashSet::new();
+    while let Ok(event) = rx.recv_timeout(Duration::from_millis(500)) {
+        let event = event.unwrap();
+        assert_ne!(event.flag(), Some(Flag::Rescan));
+        if event.kind.is_create() {
+            created.extend(event.paths);
+        }
+    }
+    assert!(created.is_superset(&files));
+
+    // the new directory is watched
+    fs::write(dir.path().join("sub").join("late"), "").unwrap();
+    let event = rx.recv_timeout(Duration::from_secs(5)).unwrap().unwrap();
+    assert_eq!(event.paths, [dir.path().join("sub").join("late")]);
+}
+
+#[test]
+fn inotify_lists_watches() {
+    use std::{fs, time::Duration};
+
+    let dir = tempfile::tempdir().unwrap();
+    let tree = dir.path().join("tree");
+    let gone = dir.path().join("gone");
+    let missing = dir.path().join("missing");
+    fs::create_dir_all(tree.join("a/b")).unwrap();
+    fs::create_dir(&gone).unwrap();
+
+    let (tx, rx) = std::sync::mpsc::channel();
+    let config = Config::default().with_pending_watches(true);
+    let mut watcher = INotifyWatcher::new(tx, config).unwrap();
+    watcher.watch(&tree, RecursiveMode::Recursive).unwrap();
+    watcher.watch(&gone, RecursiveMode::NonRecursive).unwrap();
+    watcher.watch(&missing, RecursiveMode::Recursive).unwrap();
+    fs::remove_dir(&gone).unwrap();
+    // the removal is reported before the watch is dropped
+    while rx.recv_timeout(Duration::from_millis(100)).is_ok() {}
+
+    let info = |path: &Path, recursive_mode, count, status| WatchInfo {
+        path: path.to_path_buf(),
+        recursive_mode,
+        count,
+        status,
+    };
+    assert_eq!(
+        watcher.watches().unwrap(),
+        [
+            info(&gone, RecursiveMode::NonRecursive, 0, WatchStatus::Pending),
+            info(&missing, RecursiveMode::Recursive, 0, WatchStatus::Pending),
+            info(&tree, RecursiveMode::Recursive, 3, WatchStatus::Active),
+        ]
+    );
+
+    // without pending watches, removed paths stay removed
+    let (tx, rx) = std::sync::mpsc::channel();
+    let mut watcher = INotifyWatcher::new(tx, Config::default()).unwrap();
+    watcher.watch(&tree, RecursiveMode::Recursive).unwrap();
+    fs::remove_dir_all(&tree).unwrap();
+    while rx.recv_timeout(Duration::from_millis(100)).is_ok() {}
+    assert_eq!(
+        watcher.watches().unwrap(),
+        [info(
+            &tree,
+            RecursiveMode::Recursive,
+            0,
+            WatchStatus::Removed
+        )]
+    );
+}
+
+#[test]
+fn inotify_follows_symlinks_by_policy() {
+    use std::{fs, os::unix::fs::symlink};
+
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().join("root");
+    let outside = dir.path().join("outside");
+    fs::create_dir_all(root.join("inside")).unwrap();
+    fs::create_dir(&outside).unwrap();
+    symlink(root.join("inside"), root.join("to_inside")).unwrap();
+    symlink(&outside, root.join("to_outside")).unwrap();
+    symlink(&root, root.join("inside/loop")).unwrap();
+
+    let cases = [
+        (SymlinkPolicy::DontFollow, vec!["", "inside"]),
+        (
+            SymlinkPolicy::FollowWithinRoot,
+            vec!["", "inside", "to_inside"],
+        ),
+        (
+            SymlinkPolicy::Follow,
+            vec!["", "inside", "to_inside", "to_outside"],
+        ),
+    ];
+    for (policy, expected) in cases {
+        let config = Config::default().with_symlink_policy(policy);
+        let mut watcher = INotifyWatcher::new(|_: Result<Event>| {}, config).unwrap();
+        watcher.watch(&root, RecursiveMode::Recursive).unwrap();
+        let mut watched = watcher.watched_paths().unwrap().native;
+        watched.sort();
+        let expected: Vec<_> = expected.iter().map(|path| root.join(path)).collect();
+        assert_eq!(watched, expected, "{:?}", policy);
+    }
+}
+
+#[test]
+fn inotify_polls_beyond_the_watch_limit() {
+    use std::{collections::HashSet, fs, sync::mpsc, time::Duration};
+
+    let dir = tempfile::tempdir().unwrap();
+    for sub in ["a", "b", "c"] {
+        fs::create_dir(dir.path().join(sub)).unwrap();
+    }
+    let (tx, rx) = mpsc::channel();
+    let config = Config::default()
+        .with_poll_interval(Duration::from_millis(50))
+        .with_scan_complete_events(true);
+    let fallback = PollWatcher::new(tx.clone(), config.clone()).unwrap();
+    let mut event_loop =
+        EventLoop::new(Inotify::init().unwrap(), Box::new(tx), config, None).unwrap();
+    event_loop.fallback = Some(Fallback {
+        watcher: fallback,
+        polled: HashMap::new(),
+    });
+    // as if `max_user_watches` only allowed for the root and one directory
+    event_loop.add_watch = |inotify, path, mask| {
+        if path.ends_with("b") || path.ends_with("c") {
+            Err(io::Error::from_raw_os_error(libc::ENOSPC))
+        } else {
+            inotify.add_watch(path, mask)
+        }
+    };
+    let mut watcher = INotifyWatcher::from_event_loop(event_loop, WatchFilters::default());
+    watcher.watch(dir.path(), RecursiveMode::Recursive).unwrap();
+
+    let watched = watcher.watched_paths().unwrap();
+    assert_eq!(watched.native.len(), 2);
+    assert_eq!(watched.native[0], dir.path());
+    assert_eq!(watched.polled.len(), 2);
+    // wait for the first scan of the polled paths, which reports nothing: the second scan to
+    // end from now on started after they were added
+    while rx.try_recv().is_ok() {}
+    let mut scans = 0;
+    while scans < 2 {
+        let event = rx.recv_timeout(Duration::from_secs(5)).unwrap().unwrap();
+        if event.flag() == Some(Flag::ScanComplete) {
+            scans += 1;
+        }
+    }
+    let mut files = HashSet::new();
+    for sub in ["a", "b", "c"] {
+        let file = dir.path().join(sub).join("file");
+        fs::write(&file, "").unwrap();
+        files.insert(file);
+    }
+
+    // both sources report to the one handler
+    let mut created = HashSet::new();
+    while !created.is_superset(&files) {
+        let event = rx.recv_timeout(Duration::from_secs(5)).unwrap().unwrap();
+        if event.kind.is_create() {
+            created.extend(event.paths);
+        }
+    }
+    assert_eq!(created, files);
+
+    watcher.unwatch(dir.path()).unwrap();
+    assert_eq!(watcher.watched_paths().unwrap(), WatchedPaths::default());
+}
@@ -5,7 +5,8 @@
 //! pieces of kernel code termed filters.
 
 use super::event::*;
-use super::{Error, EventHandler, RecursiveMode, Result, Watcher, Config};
+use super::{Config, Error, EventHandler, RecursiveMode, Result, WatchInfo, WatchOptions, Watcher};
+use crate::filter::{filtered_handler, WatchFilters};
 use crate::{unbounded, Receiver, Sender};
 use kqueue::{EventData, EventFilter, FilterFlag, Ident};
 use std::collections::HashMap;
@@ -41,11 +42,13 @@ struct EventLoop {
 pub struct KqueueWatcher {
     channel: Sender<EventLoopMsg>,
     waker: Arc<mio::Waker>,
+    filters: WatchFilters,
 }
 
 enum EventLoopMsg {
     AddWatch(PathBuf, RecursiveMode, Sender<Result<()>>),
     RemoveWatch(PathBuf, Sender<Result<()>>),
+    WatchedPaths(Sender<Vec<PathBuf>>),
     Shutdown,
 }
 
@@ -130,6 +133,9 @@ impl EventLoop {
                 EventLoopMsg::RemoveWatch(path, tx) => {
                     let _ = tx.send(self.remove_watch(path, false));
                 }
+                EventLoopMsg::WatchedPaths(tx) => {
+                    let _ = tx.send(self.watches.keys().cloned().collect());
+                }
                 EventLoopMsg::Shutdown => {
                     self.running = false;
                     break;
@@ -351,34 +357,43 @@ fn map_walkdir_error(e: walkdir::Error) -> Error {
 }
 
 impl KqueueWatcher {
-    fn from_event_handler(event_handler: Box<dyn EventHandler>) -> Result<Self> {
+    fn from_event_handler(
+        event_handler: Box<dyn EventHandler>,
+        filters: WatchFilters,
+    ) -> Result<Self> {
         let kqueue = kqueue::Watcher::new()?;
         let event_loop = EventLoop::new(kqueue, event_handler)?;
         let channel = event_loop.event_loop_tx.clone();
         let waker = event_loop.event_loop_waker.clone();
         event_loop.run();
-        Ok(KqueueWatcher { channel, waker })
+        Ok(KqueueWatcher {
+            channel,
+            waker,
+            filters,
+        })
     }
 
-    fn watch_inner(&mut self, path: &Path, recursive_mode: RecursiveMode) -> Result<()> {
+    fn watch_inner(&mut self, path: &Path, options: WatchOptions) -> Result<()> {
         let pb = if path.is_absolute() {
             path.to_owned()
         } else {
             let p = env::current_dir().map_err(Error::io)?;
             p.join(path)
         };
-        let (tx, rx) = unbounded();
-        let msg = EventLoopMsg::AddWatch(pb, recursive_mode, tx);
-
-        self.channel
-            .send(msg)
-            .map_err(|e| Error::generic(&e.to_string()))?;
-        self.waker
-            .wake()
-            .map_err(|e| Error::generic(&e.to_string()))?;
-        rx.recv()
-            .unwrap()
-            .map_err(|e| Error::generic(&e.to_string()))
+        self.filters.add(pb.clone(), options, || {
+            let (tx, rx) = unbounded();
+            let msg = EventLoopMsg::AddWatch(pb, options.recursive_mode(), tx);
+
+            self.channel
+                .send(msg)
+                .map_err(|e| Error::generic(&e.to_string()))?;
+            self.waker
+                .wake()
+                .map_err(|e| Error::generic(&e.to_string()))?;
+            rx.recv()
+                .unwrap()
+                .map_err(|e| Error::generic(&e.to_string()))
+        })
     }
 
     fn unwatch_inner(&mut self, path: &Path) -> Result<()> {
@@ -388,6 +403,7 @@ impl KqueueWatcher {
             let p = env::current_dir().map_err(Error::io)?;
             p.join(path)
         };
+        self.filters.remove(&pb);
         let (tx, rx) = unbounded();
         let msg = EventLoopMsg::RemoveWatch(pb, tx);
 
@@ -405,18 +421,43 @@ impl KqueueWatcher {
 
 impl Watcher for KqueueWatcher {
     /// Create a new watcher.
-    fn new<F: EventHandler>(event_handler: F, _config: Config) -> Result<Self> {
-        Self::from_event_handler(Box::new(event_handler))
+    fn new<F: EventHandler>(event_handler: F, config: Config) -> Result<Self> {
+        let (event_handler, filters, _) = filtered_handler(event_handler, &config);
+        Self::from_event_handler(event_handler, filters)
     }
 
     fn watch(&mut self, path: &Path, recursive_mode: RecursiveMode) -> Result<()> {
-        self.watch_inner(path, recursive_mode)
+        self.watch_inner(path, WatchOptions::new(recursive_mode))
+    }
+
+    fn watch_with(&mut self, path: &Path, options: WatchOptions) -> Result<()> {
+        self.watch_inner(path, options)
     }
 
     fn unwatch(&mut self, path: &Path) -> Result<()> {
         self.unwatch_inner(path)
     }
 
+    fn watches(&self) -> Result<Vec<WatchInfo>> {
+        let (tx, rx) = unbounded();
+        self.channel
+            .send(EventLoopMsg::WatchedPaths(tx))
+            .map_err(|e| Error::generic(&e.to_string()))?;
+        self.waker
+            .wake()
+            .map_err(|e| Error::generic(&e.to_string()))?;
+        let watched = rx.recv().map_err(|e| Error::generic(&e.to_string()))?;
+        // a recursive watch adds a kqueue watch for every entry below its path
+        Ok(self.filters.watch_infos(|path, recursive_mode| {
+            watched
+                .iter()
+                .filter(|p| {
+                    p.as_path() == path || (recursive_mode.is_recursive() && p.starts_with(path))
+                })
+                .count()
+        }))
+    }
+
     fn kind() -> crate::WatcherKind {
         crate::WatcherKind::Kqueue
     }
@@ -17,6 +17,8 @@
 //! - `macos_fsevent` enabled by default, for fsevent backend on macos
 //! - `macos_kqueue` for kqueue backend on macos
 //! - `crossbeam-channel` enabled by default, see below
+//! - `async` for an async `Stream` of events, see below
+//! - `gitignore` for skipping paths ignored by git, see `Config::with_gitignore`
 //!
 //! ### Serde
 //!
@@ -38,6 +40,16 @@
 //! // Alternatively macos_fsevent instead of macos_kqueue
 //! ```
 //! Note the `macos_kqueue` requirement here, otherwise no backend is available on macos.
+//!
+//! ### Async
+//!
+//! With the `async` feature, `stream::EventStream` provides the events of any watcher as a
+//! `futures_core::Stream`, without blocking inside the async runtime. It does not use
+//! crossbeam-channel, so it is also an option for tokio users.
+//!
+//! ```toml
+//! notify = { version = "5.0.0", features = ["async"] }
+//! ```
 //! 
 //! # Known Problems
 //! 
@@ -82,6 +94,9 @@
 //! ```
 //! 
 //! Note that the [PollWatcher] is not restricted by this limitation, so it may be an alternative if your users can't increase the limit.
+//! If the process has the `CAP_SYS_ADMIN` capability, the [FanotifyWatcher] watches a whole filesystem with a single mark
+//! and is not restricted by this limitation either.
+//! The [HybridWatcher] keeps using inotify as far as the limit allows and polls the remaining directories.
 //! 
 //! # Examples
 //! 
@@ -137,10 +152,13 @@
 
 #![deny(missing_docs)]
 
-pub use config::{Config, RecursiveMode};
+pub use config::{
+    Config, EventKindMask, IgnoreRules, PollSchedule, RecursiveMode, SymlinkPolicy, WatchOptions,
+};
 pub use error::{Error, ErrorKind, Result};
 pub use event::{Event, EventKind};
-use std::path::Path;
+pub use hash::{ContentHasher, HashStrategy};
+use std::path::{Path, PathBuf};
 
 #[allow(dead_code)]
 #[cfg(feature = "crossbeam-channel")]
@@ -185,15 +203,20 @@ pub(crate) fn bounded<T>(cap: usize) -> (BoundSender<T>, Receiver<T>) {
 #[cfg(all(target_os = "macos", not(feature = "macos_kqueue")))]
 pub use crate::fsevent::FsEventWatcher;
 #[cfg(target_os = "linux")]
+pub use crate::fanotify::FanotifyWatcher;
+#[cfg(target_os = "linux")]
+pub use crate::hybrid::HybridWatcher;
+#[cfg(target_os = "linux")]
 pub use crate::inotify::INotifyWatcher;
 #[cfg(any(
     target_os = "freebsd",
     target_os = "openbsd",
     target_os = "netbsd",
-    target_os = "dragonflybsd",
+    target_os = "dragonfly",
     all(target_os = "macos", feature = "macos_kqueue")
 ))]
 pub use crate::kqueue::KqueueWatcher;
+pub use fake::{FakeController, FakeWatcher};
 pub use null::NullWatcher;
 pub use poll::PollWatcher;
 #[cfg(target_os = "windows")]
@@ -202,11 +225,15 @@ pub use windows::ReadDirectoryChangesWatcher;
 #[cfg(all(target_os = "macos", not(feature = "macos_kqueue")))]
 pub mod fsevent;
 #[cfg(target_os = "linux")]
+pub mod fanotify;
+#[cfg(target_os = "linux")]
+pub mod hybrid;
+#[cfg(target_os = "linux")]
 pub mod inotify;
 #[cfg(any(
     target_os = "freebsd",
     target_os = "openbsd",
-    target_os = "dragonflybsd",
+    target_os = "dragonfly",
     target_os = "netbsd",
     all(target_os = "macos", feature = "macos_kqueue")
 ))]
@@ -215,11 +242,20 @@ pub mod kqueue;
 pub mod windows;
 
 pub mod event;
+pub mod fake;
 pub mod null;
 pub mod poll;
+#[cfg(feature = "replay")]
+pub mod replay;
+#[cfg(feature = "async")]
+pub mod stream;
+pub mod tree;
 
 mod config;
 mod error;
+mod filter;
+mod gitignore;
+mod hash;
 
 /// The set of requirements for watcher event handling functions.
 ///
@@ -260,6 +296,12 @@ impl EventHandler for crossbeam_channel::Sender<Result<Event>> {
     }
 }
 
+impl EventHandler for Box<dyn EventHandler> {
+    fn handle_event(&mut self, event: Result<Event>) {
+        (**self).handle_event(event);
+    }
+}
+
 impl EventHandler for std::sync::mpsc::Sender<Result<Event>> {
     fn handle_event(&mut self, event: Result<Event>) {
         let _ = self.send(event);
@@ -272,6 +314,10 @@ impl EventHandler for std::sync::mpsc::Sender<Result<Event>> {
 pub enum WatcherKind {
     /// inotify backend (linux)
     Inotify,
+    /// fanotify backend (linux)
+    Fanotify,
+    /// inotify backend polling beyond the watch limit (linux)
+    Hybrid,
     /// FS-Event backend (mac)
     Fsevent,
     /// KQueue backend (bsd,optionally mac)
@@ -282,6 +328,40 @@ pub enum WatcherKind {
     ReadDirectoryChangesWatcher,
     /// Fake watcher for testing
     NullWatcher,
+    /// Watcher driven by test code
+    FakeWatcher,
+    /// Watcher playing back recorded events (replay feature)
+    ReplayWatcher,
+}
+
+/// A path being watched, see [Watcher::watches]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WatchInfo {
+    /// The path given to [Watcher::watch]
+    pub path: PathBuf,
+
+    /// How the path is watched
+    pub recursive_mode: RecursiveMode,
+
+    /// Kernel watches placed for the path and below it, or paths tracked by a polling watcher
+    pub count: usize,
+
+    /// Whether events are delivered for the path
+    pub status: WatchStatus,
+}
+
+/// Whether events are delivered for a watched path, see [WatchInfo]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum WatchStatus {
+    /// Events are delivered
+    Active,
+    /// The path doesn't exist, events are delivered once it appears
+    Pending,
+    /// Parts of the tree are polled since the kernel ran out of watches, see `HybridWatcher`
+    Polled,
+    /// The path was removed, no events are delivered until it is watched again
+    Removed,
 }
 
 /// Type that can deliver file activity notifications
@@ -311,6 +391,36 @@ pub trait Watcher {
     /// [#166]: https://github.com/notify-rs/notify/issues/166
     fn watch(&mut self, path: &Path, recursive_mode: RecursiveMode) -> Result<()>;
 
+    /// Begin watching a new path with per-watch options, such as the kinds of events to deliver.
+    ///
+    /// Like [Watcher::watch] otherwise. Watching a path again replaces its options.
+    ///
+    /// ```no_run
+    /// # use notify::{Event, EventKindMask, RecursiveMode, Result, Watcher, WatchOptions};
+    /// # use std::path::Path;
+    /// # fn main() -> Result<()> {
+    /// let mut watcher = notify::recommended_watcher(|e: Result<Event>| println!("{:?}", e))?;
+    /// // only new and removed files, without the writes to them
+    /// let options = WatchOptions::new(RecursiveMode::Recursive)
+    ///     .with_event_kinds(EventKindMask::CREATE | EventKindMask::REMOVE);
+    /// watcher.watch_with(Path::new("/var/log"), options)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Like [Watcher::watch], and if the watcher can't filter events by kind while
+    /// [WatchOptions::with_event_kinds] is set.
+    fn watch_with(&mut self, path: &Path, options: WatchOptions) -> Result<()> {
+        match options.event_kinds() {
+            None => self.watch(path, options.recursive_mode()),
+            Some(_) => Err(Error::generic(
+                "event kind filters are not supported by this watcher",
+            )),
+        }
+    }
+
     /// Stop watching a path.
     ///
     /// # Errors
@@ -332,6 +442,17 @@ pub trait Watcher {
         Ok(false)
     }
 
+    /// Returns the watched paths, with how they are watched and their status, sorted by path.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the watcher can't list its watches.
+    fn watches(&self) -> Result<Vec<WatchInfo>> {
+        Err(Error::generic(
+            "listing watches is not supported by this watcher",
+        ))
+    }
+
     /// Returns the watcher kind, allowing to perform backend-specific tasks
     fn kind() -> WatcherKind
     where
@@ -352,7 +473,7 @@ pub type RecommendedWatcher = ReadDirectoryChangesWatcher;
     target_os = "freebsd",
     target_os = "openbsd",
     target_os = "netbsd",
-    target_os = "dragonflybsd",
+    target_os = "dragonfly",
     all(target_os = "macos", feature = "macos_kqueue")
 ))]
 pub type RecommendedWatcher = KqueueWatcher;
@@ -364,7 +485,7 @@ pub type RecommendedWatcher = KqueueWatcher;
     target_os = "freebsd",
     target_os = "openbsd",
     target_os = "netbsd",
-    target_os = "dragonflybsd"
+    target_os = "dragonfly"
 )))]
 pub type RecommendedWatcher = PollWatcher;
 
@@ -393,6 +514,7 @@ mod tests {
     fn test_debug_impl() {
         macro_rules! assert_debug_impl {
             ($t:ty) => {{
+                #[allow(dead_code)]
                 trait NeedsDebug: std::fmt::Debug {}
                 impl NeedsDebug for $t {}
             }};
@@ -413,6 +535,7 @@ mod tests {
         assert_debug_impl!(event::RenameMode);
         assert_debug_impl!(Event);
         assert_debug_impl!(EventKind);
+        assert_debug_impl!(FakeWatcher);
         assert_debug_impl!(NullWatcher);
         assert_debug_impl!(PollWatcher);
         assert_debug_impl!(RecommendedWatcher);
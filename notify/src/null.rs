@@ -4,7 +4,7 @@
 
 use crate::Config;
 
-use super::{RecursiveMode, Result, Watcher};
+use super::{RecursiveMode, Result, WatchInfo, WatchOptions, Watcher};
 use std::path::Path;
 
 /// Stub `Watcher` implementation
@@ -18,6 +18,10 @@ impl Watcher for NullWatcher {
         Ok(())
     }
 
+    fn watch_with(&mut self, path: &Path, options: WatchOptions) -> Result<()> {
+        Ok(())
+    }
+
     fn unwatch(&mut self, path: &Path) -> Result<()> {
         Ok(())
     }
@@ -33,6 +37,10 @@ impl Watcher for NullWatcher {
         Ok(false)
     }
 
+    fn watches(&self) -> Result<Vec<WatchInfo>> {
+        Ok(Vec::new())
+    }
+
     fn kind() -> crate::WatcherKind {
         crate::WatcherKind::NullWatcher
     }
@@ -3,7 +3,12 @@
 //! Checks the `watch`ed paths periodically to detect changes. This implementation only uses
 //! Rust stdlib APIs and should work on all of the platforms it supports.
 
-use crate::{EventHandler, RecursiveMode, Watcher, Config};
+use crate::event::{Event, EventKind, Flag};
+use crate::filter::{filtered_handler, WatchFilters};
+use crate::{
+    unbounded, Config, EventHandler, IgnoreRules, PollSchedule, Receiver, RecursiveMode, Sender,
+    WatchInfo, WatchOptions, Watcher,
+};
 use std::{
     collections::HashMap,
     path::{Path, PathBuf},
@@ -12,92 +17,490 @@ use std::{
         Arc, Mutex,
     },
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
-use data::{DataBuilder, WatchData};
-mod data {
+use data::{DataBuilder, ScanPool, Snapshot, WatchData};
+pub(crate) mod data {
     use crate::{
-        event::{CreateKind, DataChange, Event, EventKind, MetadataKind, ModifyKind, RemoveKind},
-        EventHandler,
+        event::{
+            CreateKind, DataChange, Event, EventKind, MetadataKind, ModifyKind, RemoveKind,
+            RenameMode,
+        },
+        EventHandler, HashStrategy, IgnoreRules, RecursiveMode, SymlinkPolicy, WatchInfo,
+        WatchStatus,
     };
     use filetime::FileTime;
     use std::{
         cell::RefCell,
-        collections::{hash_map::RandomState, HashMap},
+        collections::{BTreeMap, HashMap, HashSet, VecDeque},
         fmt::{self, Debug},
         fs::{self, File, Metadata},
-        hash::{BuildHasher, Hasher},
-        io::{self, Read},
+        io::{self, BufReader, BufWriter, Read, Write},
         path::{Path, PathBuf},
+        sync::{Arc, Condvar, Mutex},
+        thread,
         time::Instant,
     };
-    use walkdir::WalkDir;
 
     /// Builder for [`WatchData`] & [`PathData`].
-    pub(super) struct DataBuilder {
+    pub(crate) struct DataBuilder {
         emitter: EventEmitter,
 
-        // TODO: May allow user setup their custom BuildHasher / BuildHasherDefault
-        // in future.
-        build_hasher: Option<RandomState>,
-
-        // current timestamp for building Data.
-        now: Instant,
+        // everything needed to read `PathData`, shared with scans.
+        scanner: Scanner,
     }
 
     impl DataBuilder {
-        pub(super) fn new<F>(event_handler: F, compare_content: bool) -> Self
+        pub(crate) fn new<F>(
+            event_handler: F,
+            hasher: Option<HashStrategy>,
+            ignore: Option<IgnoreRules>,
+            scan_pool: Option<Arc<ScanPool>>,
+            symlink_policy: SymlinkPolicy,
+        ) -> Self
         where
             F: EventHandler,
         {
             Self {
                 emitter: EventEmitter::new(event_handler),
-                build_hasher: compare_content.then(RandomState::default),
-                now: Instant::now(),
+                scanner: Scanner {
+                    hasher,
+                    ignore,
+                    symlink_policy,
+                    now: Instant::now(),
+                    pool: scan_pool,
+                },
             }
         }
 
         /// Update internal timestamp.
-        pub(super) fn update_timestamp(&mut self) {
-            self.now = Instant::now();
+        pub(crate) fn update_timestamp(&mut self) {
+            self.scanner.now = Instant::now();
+        }
+
+        /// Get a [`Scanner`] for the current timestamp.
+        ///
+        /// Scans don't need the builder, so it can be unlocked while they run.
+        pub(crate) fn scanner(&self) -> Scanner {
+            self.scanner.clone()
         }
 
         /// Create [`WatchData`].
         ///
         /// This function will return `Err(_)` if can not retrieve metadata from
         /// the path location. (e.g., not found).
-        pub(super) fn build_watch_data(
+        #[cfg(target_os = "linux")]
+        pub(crate) fn build_watch_data(
             &self,
             root: PathBuf,
             is_recursive: bool,
+            allow_pending: bool,
         ) -> Option<WatchData> {
-            WatchData::new(self, root, is_recursive)
+            WatchData::new(self, root, is_recursive, allow_pending)
         }
 
-        /// Create [`PathData`].
-        fn build_path_data(&self, meta_path: &MetaPath) -> PathData {
-            PathData::new(self, meta_path)
+        /// Emit io error event.
+        pub(crate) fn emit_io_err(&self, err: io::Error, path: &Path) {
+            self.emitter.emit_io_err(err, path)
+        }
+
+        /// Emit event.
+        pub(crate) fn emit_ok(&self, event: Event) {
+            self.emitter.emit_ok(event)
         }
     }
 
     impl Debug for DataBuilder {
         fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
             f.debug_struct("DataBuilder")
-                .field("build_hasher", &self.build_hasher)
-                .field("now", &self.now)
+                .field("scanner", &self.scanner)
                 .finish()
         }
     }
 
+    /// Reads [`PathData`] from the filesystem, on one or more threads.
+    #[derive(Debug, Clone)]
+    pub(crate) struct Scanner {
+        // content hashes are only read with a strategy.
+        hasher: Option<HashStrategy>,
+
+        // paths skipped while scanning.
+        ignore: Option<IgnoreRules>,
+
+        // links read as files rather than what they point to.
+        symlink_policy: SymlinkPolicy,
+
+        // current timestamp for building Data.
+        now: Instant,
+
+        // threads helping the calling one to read directories.
+        pool: Option<Arc<ScanPool>>,
+    }
+
+    impl Scanner {
+        /// Scan a watched path, see [`WatchData::from_scan`].
+        pub(crate) fn scan(&self, root: &Path, is_recursive: bool) -> Scan {
+            self.scan_depth(root, root, WatchData::dir_scan_depth(is_recursive))
+        }
+
+        /// Get all `PathData` of `start` and below, down to `max_depth`.
+        ///
+        /// `start` is `root` or a path below the watched `root`. Every directory
+        /// is a task of its own, taken by the next idle worker of the pool.
+        fn scan_depth(&self, root: &Path, start: &Path, max_depth: usize) -> Scan {
+            let queue = Arc::new(ScanQueue {
+                scanner: self.clone(),
+                max_depth,
+                canonical_root: fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf()),
+                state: Mutex::new(QueueState {
+                    tasks: Vec::new(),
+                    busy: 0,
+                    helpers: 0,
+                    found: self.empty_scan(),
+                }),
+                changed: Condvar::new(),
+            });
+
+            let mut scan = self.empty_scan();
+            // the root is always followed, a link below it only as the policy says.
+            let is_symlink = start != root
+                && fs::symlink_metadata(start).map_or(false, |m| m.file_type().is_symlink());
+            // a missing root is reported when watching it, later scans find nothing.
+            match queue.read_entry(None, start.to_path_buf(), is_symlink, 0, &mut scan) {
+                Ok(Some(task)) => {
+                    let mut state = queue.state.lock().unwrap_or_else(|e| e.into_inner());
+                    state.tasks.push(task);
+                }
+                _ => return scan,
+            }
+            if let Some(ref pool) = self.pool {
+                pool.request(&queue);
+            }
+            let mut found = queue.work();
+            if let Some(ref pool) = self.pool {
+                pool.withdraw(&queue);
+            }
+            let mut state = queue.state.lock().unwrap_or_else(|e| e.into_inner());
+            while state.helpers > 0 {
+                state = queue.changed.wait(state).unwrap_or_else(|e| e.into_inner());
+            }
+            found.all_path_data.append(&mut state.found.all_path_data);
+            found.errors.append(&mut state.found.errors);
+            scan.all_path_data.append(&mut found.all_path_data);
+            scan.errors = found.errors;
+            scan
+        }
+
+        fn empty_scan(&self) -> Scan {
+            Scan {
+                now: self.now,
+                all_path_data: BTreeMap::new(),
+                errors: Vec::new(),
+            }
+        }
+    }
+
+    /// What a [`Scanner`] found.
     #[derive(Debug)]
-    pub(super) struct WatchData {
+    pub(crate) struct Scan {
+        now: Instant,
+        all_path_data: BTreeMap<PathBuf, PathData>,
+
+        // entries that could not be read, to be emitted.
+        errors: Vec<crate::Error>,
+    }
+
+    /// Directories left to read, shared by the workers of a scan.
+    struct ScanQueue {
+        scanner: Scanner,
+        max_depth: usize,
+
+        // the watched root with symlinks resolved, for `SymlinkPolicy::FollowWithinRoot`.
+        canonical_root: PathBuf,
+        state: Mutex<QueueState>,
+        changed: Condvar,
+    }
+
+    struct QueueState {
+        tasks: Vec<ScanTask>,
+
+        // tasks being worked on, which may add more.
+        busy: usize,
+
+        // threads of the pool taking part in the scan.
+        helpers: usize,
+
+        // what the helpers found.
+        found: Scan,
+    }
+
+    struct ScanTask {
+        dir: PathBuf,
+
+        // `dir` with symlinks resolved, to detect loops without inode numbers.
+        canonical: PathBuf,
+
+        // device and inode numbers of `dir` and the directories above it.
+        ancestors: Vec<(u64, u64)>,
+        depth: usize,
+    }
+
+    impl ScanQueue {
+        /// Take tasks on a thread of the pool, adding what was found to the state.
+        fn help(&self) {
+            self.state.lock().unwrap_or_else(|e| e.into_inner()).helpers += 1;
+            let mut scan = self.work();
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            state.found.all_path_data.append(&mut scan.all_path_data);
+            state.found.errors.append(&mut scan.errors);
+            state.helpers -= 1;
+            self.changed.notify_all();
+        }
+
+        /// Take tasks until there are none left, returning what was found.
+        fn work(&self) -> Scan {
+            let mut scan = self.scanner.empty_scan();
+            while let Some(task) = self.next_task() {
+                let subdirs = self.scan_dir(&task, &mut scan);
+
+                let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+                state.tasks.extend(subdirs);
+                state.busy -= 1;
+                self.changed.notify_all();
+            }
+            scan
+        }
+
+        /// Wait for a task, or for all of them to be done.
+        fn next_task(&self) -> Option<ScanTask> {
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            loop {
+                if let Some(task) = state.tasks.pop() {
+                    state.busy += 1;
+                    return Some(task);
+                }
+                if state.busy == 0 {
+                    return None;
+                }
+                state = self.changed.wait(state).unwrap_or_else(|e| e.into_inner());
+            }
+        }
+
+        /// Read the entries of `task.dir`, returning the directories to read next.
+        fn scan_dir(&self, task: &ScanTask, scan: &mut Scan) -> Vec<ScanTask> {
+            let mut subdirs = Vec::new();
+            //
+            // QUESTION: should we ignore IO Error?
+            //
+            // current implementation ignore some IO error, e.g.,
+            //
+            // - unreadable directories and entries
+            // - dangling symlinks
+            // - all read error when hashing
+            //
+            // but the code also interest with `fs::metadata()` error and
+            // propagate to event handler. It may not consistent.
+            //
+            // FIXME: Should we emit all IO error events? Or ignore them all?
+            let entries = match fs::read_dir(&task.dir) {
+                Ok(entries) => entries,
+                Err(_) => return subdirs,
+            };
+            let depth = task.depth + 1;
+            for entry in entries.filter_map(|entry| entry.ok()) {
+                let path = entry.path();
+                // ignored directories are not read at all.
+                if let Some(ref ignore) = self.scanner.ignore {
+                    if ignore.is_ignored(&path) {
+                        continue;
+                    }
+                }
+
+                let is_symlink = entry.file_type().map_or(false, |t| t.is_symlink());
+                match self.read_entry(Some(task), path.clone(), is_symlink, depth, scan) {
+                    Ok(subdir) => subdirs.extend(subdir),
+                    // a link removed meanwhile
+                    Err(_) if is_symlink => {}
+                    Err(e) => scan.errors.push(crate::Error::io(e).add_path(path)),
+                }
+            }
+            subdirs
+        }
+
+        /// Add the `PathData` of `path` to `scan`, returning the task to read
+        /// it if it is a directory to descend into.
+        fn read_entry(
+            &self,
+            parent: Option<&ScanTask>,
+            path: PathBuf,
+            is_symlink: bool,
+            depth: usize,
+            scan: &mut Scan,
+        ) -> io::Result<Option<ScanTask>> {
+            let target = if is_symlink { self.follow(&path) } else { None };
+            let metadata = if is_symlink && target.is_none() {
+                fs::symlink_metadata(&path)?
+            } else {
+                fs::metadata(&path)?
+            };
+
+            let mut task = None;
+            if metadata.is_dir() && depth < self.max_depth {
+                let id = PathData::get_id(&metadata);
+                let canonical = match (target, parent) {
+                    (Some(target), _) => target,
+                    (None, Some(parent)) => {
+                        parent.canonical.join(path.file_name().unwrap_or_default())
+                    }
+                    (None, None) => fs::canonicalize(&path).unwrap_or_else(|_| path.clone()),
+                };
+                let mut ancestors = Vec::new();
+                if let Some(parent) = parent {
+                    // a directory containing itself would be read forever.
+                    let is_loop = match id {
+                        Some(id) => parent.ancestors.contains(&id),
+                        None => parent.canonical.starts_with(&canonical),
+                    };
+                    if is_loop {
+                        return Ok(None);
+                    }
+                    ancestors.extend_from_slice(&parent.ancestors);
+                }
+                ancestors.extend(id);
+                task = Some(ScanTask {
+                    dir: path.clone(),
+                    canonical,
+                    ancestors,
+                    depth,
+                });
+            }
+
+            let meta_path = MetaPath::from_parts_unchecked(path, metadata);
+            let path_data = PathData::new(&self.scanner, &meta_path);
+            scan.all_path_data.insert(meta_path.into_path(), path_data);
+            Ok(task)
+        }
+
+        /// Resolve the link at `path`, if the symlink policy follows it.
+        fn follow(&self, path: &Path) -> Option<PathBuf> {
+            if self.scanner.symlink_policy == SymlinkPolicy::DontFollow {
+                return None;
+            }
+            let target = fs::canonicalize(path).ok()?;
+            match self.scanner.symlink_policy {
+                SymlinkPolicy::FollowWithinRoot if !target.starts_with(&self.canonical_root) => {
+                    None
+                }
+                _ => Some(target),
+            }
+        }
+    }
+
+    /// Threads helping with the scans of a watcher, see [`crate::Config::with_scan_workers`].
+    ///
+    /// They live as long as the watcher, waiting for scans to ask for help.
+    pub(crate) struct ScanPool {
+        threads: usize,
+        state: Mutex<PoolState>,
+        changed: Condvar,
+    }
+
+    struct PoolState {
+        // scans asking for help, once per thread.
+        queues: VecDeque<Arc<ScanQueue>>,
+        stopped: bool,
+    }
+
+    impl ScanPool {
+        /// Start `threads` threads, besides the ones asking for scans.
+        pub(crate) fn start(threads: usize) -> Arc<Self> {
+            let pool = Arc::new(ScanPool {
+                threads,
+                state: Mutex::new(PoolState {
+                    queues: VecDeque::new(),
+                    stopped: false,
+                }),
+                changed: Condvar::new(),
+            });
+            for _ in 0..threads {
+                let pool = Arc::clone(&pool);
+                // a thread that can't be started leaves the work to the others.
+                let _ = thread::Builder::new()
+                    .name("notify-rs poll scan".to_string())
+                    .spawn(move || {
+                        while let Some(queue) = pool.next_queue() {
+                            queue.help();
+                        }
+                    });
+            }
+            pool
+        }
+
+        /// Stop the threads, once they are done with their current scan.
+        pub(crate) fn stop(&self) {
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            state.stopped = true;
+            state.queues.clear();
+            self.changed.notify_all();
+        }
+
+        /// Let every thread take part in the scan of `queue`.
+        fn request(&self, queue: &Arc<ScanQueue>) {
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            if state.stopped {
+                return;
+            }
+            for _ in 0..self.threads {
+                state.queues.push_back(Arc::clone(queue));
+            }
+            self.changed.notify_all();
+        }
+
+        /// Take back the requests for `queue` no thread got to.
+        fn withdraw(&self, queue: &Arc<ScanQueue>) {
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            state.queues.retain(|q| !Arc::ptr_eq(q, queue));
+        }
+
+        /// Wait for a scan to help with, or for the pool to stop.
+        fn next_queue(&self) -> Option<Arc<ScanQueue>> {
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            loop {
+                if state.stopped {
+                    return None;
+                }
+                if let Some(queue) = state.queues.pop_front() {
+                    return Some(queue);
+                }
+                state = self.changed.wait(state).unwrap_or_else(|e| e.into_inner());
+            }
+        }
+    }
+
+    impl Debug for ScanPool {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.debug_struct("ScanPool")
+                .field("threads", &self.threads)
+                .finish()
+        }
+    }
+
+    #[derive(Debug)]
+    pub(crate) struct WatchData {
         // config part, won't change.
         root: PathBuf,
         is_recursive: bool,
 
         // current status part.
-        all_path_data: HashMap<PathBuf, PathData>,
+        // ordered, so everything below a path directly follows it.
+        all_path_data: BTreeMap<PathBuf, PathData>,
+
+        // timestamp of the latest scan merged in.
+        scanned: Instant,
+
+        // whether a scan was merged in yet, see `WatchData::deferred`.
+        has_baseline: bool,
     }
 
     impl WatchData {
@@ -106,38 +509,104 @@ mod data {
         /// # Side effect
         ///
         /// This function may send event by `data_builder.emitter`.
-        fn new(data_builder: &DataBuilder, root: PathBuf, is_recursive: bool) -> Option<Self> {
+        #[cfg(target_os = "linux")]
+        fn new(
+            data_builder: &DataBuilder,
+            root: PathBuf,
+            is_recursive: bool,
+            allow_pending: bool,
+        ) -> Option<Self> {
             // If metadata read error at `root` path, it will emit
             // a error event and stop to create the whole `WatchData`.
-            //
-            // QUESTION: inconsistent?
-            //
-            // When user try to *CREATE* a watch by `poll_watcher.watch(root, ..)`,
-            // if `root` path hit an io error, then watcher will reject to
-            // create this new watch.
-            //
-            // This may inconsistent with *POLLING* a watch. When watcher
-            // continue polling, io error at root path will not delete
-            // a existing watch. polling still working.
-            //
-            // So, consider a config file may not exists at first time but may
-            // create after a while, developer cannot watch it.
-            //
-            // FIXME: Can we always allow to watch a path, even file not
-            // found at this path?
-            if let Err(e) = fs::metadata(&root) {
+            if let Err(e) = Self::check_root(&root, allow_pending) {
                 data_builder.emitter.emit_io_err(e, &root);
                 return None;
             }
 
-            let all_path_data =
-                Self::scan_all_path_data(data_builder, root.clone(), is_recursive).collect();
+            let scan = data_builder.scanner.scan(&root, is_recursive);
+            Some(Self::from_scan(data_builder, root, is_recursive, scan))
+        }
+
+        /// Check that `root` can be watched.
+        ///
+        /// A missing root is rejected, unless pending watches are allowed:
+        /// it is then watched like any other and picked up by the scan once
+        /// it exists. A root that disappears later on keeps being polled.
+        pub(crate) fn check_root(root: &Path, allow_pending: bool) -> io::Result<()> {
+            match fs::metadata(root) {
+                Err(e) if allow_pending && e.kind() == io::ErrorKind::NotFound => Ok(()),
+                Err(e) => Err(e),
+                Ok(_) => Ok(()),
+            }
+        }
+
+        /// Create a `WatchData` from the [`Scanner::scan`] of `root`.
+        ///
+        /// # Side effect
+        ///
+        /// This function may emit the errors of the scan by `data_builder.emitter`.
+        pub(crate) fn from_scan(
+            data_builder: &DataBuilder,
+            root: PathBuf,
+            is_recursive: bool,
+            scan: Scan,
+        ) -> Self {
+            data_builder.emitter.emit_errors(scan.errors);
+
+            Self {
+                root,
+                is_recursive,
+                all_path_data: scan.all_path_data,
+                scanned: scan.now,
+                has_baseline: true,
+            }
+        }
 
-            Some(Self {
+        /// Create a `WatchData` without scanning `root`.
+        ///
+        /// The first scan merged in becomes the data changes are reported
+        /// against, without emitting events for it.
+        #[cfg(target_os = "linux")]
+        pub(crate) fn deferred(root: PathBuf, is_recursive: bool) -> Self {
+            Self {
                 root,
                 is_recursive,
+                all_path_data: BTreeMap::new(),
+                scanned: Instant::now(),
+                has_baseline: false,
+            }
+        }
+
+        /// Create a `WatchData` from the data saved by a previous run,
+        /// updated with the [`Scanner::scan`] of `root`.
+        ///
+        /// # Side effect
+        ///
+        /// This function may emit events for the changes since the data was
+        /// saved by `data_builder.emitter`.
+        pub(crate) fn from_snapshot(
+            data_builder: &DataBuilder,
+            root: PathBuf,
+            saved: SavedWatch,
+            scan: Scan,
+        ) -> Self {
+            let mut all_path_data = saved.all_path_data;
+            // hashes are only compared if both runs have them.
+            if saved.hashed != data_builder.scanner.hasher.is_some() {
+                for (path, path_data) in all_path_data.iter_mut() {
+                    path_data.hash = scan.all_path_data.get(path).and_then(|new| new.hash);
+                }
+            }
+
+            let mut watch_data = Self {
+                root,
+                is_recursive: saved.is_recursive,
                 all_path_data,
-            })
+                scanned: scan.now,
+                has_baseline: true,
+            };
+            watch_data.merge_scan(data_builder, scan);
+            watch_data
         }
 
         /// Rescan filesystem and update this `WatchData`.
@@ -145,96 +614,194 @@ mod data {
         /// # Side effect
         ///
         /// This function may emit event by `data_builder.emitter`.
-        pub(super) fn rescan(&mut self, data_builder: &mut DataBuilder) {
-            // scan current filesystem.
-            for (path, new_path_data) in
-                Self::scan_all_path_data(data_builder, self.root.clone(), self.is_recursive)
-            {
-                let old_path_data = self
+        #[cfg(target_os = "linux")]
+        pub(crate) fn rescan(&mut self, data_builder: &mut DataBuilder) {
+            let scan = data_builder.scanner.scan(&self.root, self.is_recursive);
+            self.merge_scan(data_builder, scan);
+        }
+
+        /// Update this `WatchData` with a newer [`Scanner::scan`] of its root.
+        ///
+        /// Scans older than the data, e.g. started before the path was watched
+        /// again, are dropped. Returns whether anything changed.
+        ///
+        /// # Side effect
+        ///
+        /// This function may emit event by `data_builder.emitter`.
+        pub(crate) fn merge_scan(&mut self, data_builder: &DataBuilder, scan: Scan) -> bool {
+            if scan.now < self.scanned {
+                return false;
+            }
+            self.scanned = scan.now;
+            data_builder.emitter.emit_errors(scan.errors);
+            if !self.has_baseline {
+                self.has_baseline = true;
+                self.all_path_data = scan.all_path_data;
+                return false;
+            }
+
+            let renames = self.find_renames(&scan.all_path_data);
+            let mut changed = !renames.is_empty();
+
+            for (path, new_path_data) in &scan.all_path_data {
+                let mut old_path_data = self
                     .all_path_data
                     .insert(path.clone(), new_path_data.clone());
 
-                // emit event
-                let event =
-                    PathData::compare_to_event(path, old_path_data.as_ref(), Some(&new_path_data));
-                if let Some(event) = event {
+                if let Some(from) = renames.get(path) {
+                    // paths below a renamed directory are covered by its event.
+                    let moved_with_parent = path
+                        .parent()
+                        .and_then(|p| renames.get(p))
+                        .map(PathBuf::as_path)
+                        == from.parent()
+                        && path.file_name() == from.file_name();
+                    if !moved_with_parent {
+                        let event =
+                            Event::new(EventKind::Modify(ModifyKind::Name(RenameMode::Both)))
+                                .add_path(from.clone())
+                                .add_path(path.clone());
+                        data_builder.emitter.emit_ok(event);
+                    }
+                    old_path_data = self.all_path_data.get(from).cloned();
+                }
+
+                // emit events
+                let events =
+                    PathData::compare_to_events(path, old_path_data.as_ref(), Some(new_path_data));
+                changed |= !events.is_empty();
+                for event in events {
                     data_builder.emitter.emit_ok(event);
                 }
             }
+            let renamed: HashSet<_> = renames.into_values().collect();
 
             // scan for disappeared paths.
-            let mut disappeared_paths = Vec::new();
-            for (path, path_data) in self.all_path_data.iter() {
-                if path_data.last_check < data_builder.now {
-                    disappeared_paths.push(path.clone());
-                }
-            }
+            let disappeared_paths: Vec<_> = self
+                .all_path_data
+                .keys()
+                .filter(|path| !scan.all_path_data.contains_key(*path))
+                .cloned()
+                .collect();
+            changed |= !disappeared_paths.is_empty();
 
             // remove disappeared paths
             for path in disappeared_paths {
                 let old_path_data = self.all_path_data.remove(&path);
+                if renamed.contains(&path) {
+                    continue;
+                }
 
-                // emit event
-                let event = PathData::compare_to_event(path, old_path_data.as_ref(), None);
-                if let Some(event) = event {
+                // emit events
+                for event in PathData::compare_to_events(path, old_path_data.as_ref(), None) {
                     data_builder.emitter.emit_ok(event);
                 }
             }
+            changed
         }
 
-        /// Get all `PathData` by given configuration.
-        ///
-        /// # Side Effect
+        /// Pair up paths gone since the last scan with new paths of the same
+        /// file, mapping each new path to its old one.
         ///
-        /// This function may emit some IO Error events by `data_builder.emitter`.
-        fn scan_all_path_data(
-            data_builder: &'_ DataBuilder,
-            root: PathBuf,
-            is_recursive: bool,
-        ) -> impl Iterator<Item = (PathBuf, PathData)> + '_ {
-            // WalkDir return only one entry if root is a file (not a folder),
-            // so we can use single logic to do the both file & dir's jobs.
-            //
-            // See: https://docs.rs/walkdir/2.0.1/walkdir/struct.WalkDir.html#method.new
-            WalkDir::new(root)
-                .follow_links(true)
-                .max_depth(Self::dir_scan_depth(is_recursive))
-                .into_iter()
-                //
-                // QUESTION: should we ignore IO Error?
-                //
-                // current implementation ignore some IO error, e.g.,
-                //
-                // - `.filter_map(|entry| entry.ok())`
-                // - all read error when hashing
-                //
-                // but the code also interest with `fs::metadata()` error and
-                // propagate to event handler. It may not consistent.
-                //
-                // FIXME: Should we emit all IO error events? Or ignore them all?
-                .filter_map(|entry| entry.ok())
-                .filter_map(|entry| match entry.metadata() {
-                    Ok(metadata) => {
-                        let path = entry.into_path();
-
-                        let meta_path = MetaPath::from_parts_unchecked(path, metadata);
-                        let data_path = data_builder.build_path_data(&meta_path);
-
-                        Some((meta_path.into_path(), data_path))
-                    }
-                    Err(e) => {
-                        // emit event.
-                        let path = entry.into_path();
-                        data_builder.emitter.emit_io_err(e, path);
+        /// Paths are matched by device and inode number. Files also have to
+        /// keep their modification time, size and hash if there is one, to
+        /// tell renames apart from inodes reused for new files.
+        fn find_renames(&self, scanned: &BTreeMap<PathBuf, PathData>) -> HashMap<PathBuf, PathBuf> {
+            let mut disappeared: HashMap<_, _> = self
+                .all_path_data
+                .iter()
+                .filter(|(path, _)| !scanned.contains_key(*path))
+                .filter_map(|(path, data)| Some((data.id?, (path, data))))
+                .collect();
+            if disappeared.is_empty() {
+                return HashMap::new();
+            }
 
-                        None
-                    }
+            scanned
+                .iter()
+                .filter(|(path, _)| !self.all_path_data.contains_key(*path))
+                .filter_map(|(path, data)| {
+                    let (from, old) = disappeared.remove(&data.id?)?;
+                    let same = old.kind == data.kind
+                        && (old.kind == FileKind::Folder
+                            || (old.mtime == data.mtime
+                                && old.size == data.size
+                                && old.hash.zip(data.hash).map_or(true, |(a, b)| a == b)));
+                    same.then(|| (path.clone(), from.clone()))
                 })
+                .collect()
+        }
+
+        /// Update the data of `path`, without emitting events.
+        ///
+        /// Used by backends reporting changes themselves, to keep a snapshot
+        /// for [`WatchData::rescan`] current. Paths outside of this watch are
+        /// skipped. Only `path` itself is read, and the data below it dropped
+        /// if it's gone, unless `rescan` asks to read everything below it,
+        /// e.g. for a directory just created or moved in.
+        #[cfg(target_os = "linux")]
+        pub(crate) fn refresh(&mut self, data_builder: &DataBuilder, path: &Path, rescan: bool) {
+            let in_scope = if self.is_recursive {
+                path.starts_with(&self.root)
+            } else {
+                path == self.root || path.parent() == Some(&self.root)
+            };
+            if !in_scope {
+                return;
+            }
+
+            let max_depth = if rescan && (path == self.root || self.is_recursive) {
+                Self::dir_scan_depth(self.is_recursive)
+            } else {
+                // the path itself, or a direct child of a non recursive watch
+                0
+            };
+            let scan = data_builder.scanner.scan_depth(&self.root, path, max_depth);
+            if rescan || scan.all_path_data.is_empty() {
+                let below: Vec<_> = self
+                    .all_path_data
+                    .range(path.to_path_buf()..)
+                    .map(|(p, _)| p)
+                    .take_while(|p| p.starts_with(path))
+                    .cloned()
+                    .collect();
+                for p in below {
+                    self.all_path_data.remove(&p);
+                }
+            }
+            data_builder.emitter.emit_errors(scan.errors);
+            self.all_path_data.extend(scan.all_path_data);
+        }
+
+        /// Returns whether sub-directories are watched as well
+        pub(crate) fn is_recursive(&self) -> bool {
+            self.is_recursive
+        }
+
+        /// Describe this watch, see [`crate::Watcher::watches`].
+        pub(crate) fn info(&self) -> WatchInfo {
+            let recursive_mode = if self.is_recursive {
+                RecursiveMode::Recursive
+            } else {
+                RecursiveMode::NonRecursive
+            };
+            // a missing root is looked for by every scan.
+            let status = if self.all_path_data.contains_key(&self.root) {
+                WatchStatus::Active
+            } else {
+                WatchStatus::Pending
+            };
+            WatchInfo {
+                path: self.root.clone(),
+                recursive_mode,
+                count: self.all_path_data.len(),
+                status,
+            }
         }
 
         fn dir_scan_depth(is_recursive: bool) -> usize {
             if is_recursive {
-                usize::max_value()
+                usize::MAX
             } else {
                 1
             }
@@ -246,85 +813,409 @@ mod data {
     /// See [`WatchData`] for more detail.
     #[derive(Debug, Clone)]
     struct PathData {
-        /// File updated time.
-        mtime: i64,
+        /// File updated time, with nanoseconds where the platform has them.
+        mtime: FileTime,
+
+        /// File size.
+        size: u64,
+
+        /// Permission bits, only the read-only flag on non-unix platforms.
+        mode: u32,
+
+        /// Owning user and group id, zero on non-unix platforms.
+        owner: (u32, u32),
+
+        /// Type of the file.
+        kind: FileKind,
+
+        /// Device and inode number, to pair up renamed paths. Only on unix.
+        id: Option<(u64, u64)>,
 
         /// Content's hash value, only available if user request compare file
-        /// contents and read successful.
+        /// contents and read successful. Stable across runs.
         hash: Option<u64>,
+    }
+
+    /// Type of the file at a path, as far as events can tell.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum FileKind {
+        File,
+        Folder,
+        Other,
+    }
+
+    impl FileKind {
+        fn create_event(self) -> EventKind {
+            EventKind::Create(match self {
+                FileKind::File => CreateKind::File,
+                FileKind::Folder => CreateKind::Folder,
+                FileKind::Other => CreateKind::Other,
+            })
+        }
 
-        /// Checked time.
-        last_check: Instant,
+        fn remove_event(self) -> EventKind {
+            EventKind::Remove(match self {
+                FileKind::File => RemoveKind::File,
+                FileKind::Folder => RemoveKind::Folder,
+                FileKind::Other => RemoveKind::Other,
+            })
+        }
     }
 
     impl PathData {
         /// Create a new `PathData`.
-        fn new(data_builder: &DataBuilder, meta_path: &MetaPath) -> PathData {
+        fn new(scanner: &Scanner, meta_path: &MetaPath) -> PathData {
             let metadata = meta_path.metadata();
 
-            PathData {
-                mtime: FileTime::from_last_modification_time(metadata).seconds(),
-                hash: data_builder
-                    .build_hasher
-                    .as_ref()
-                    .filter(|_| metadata.is_file())
-                    .and_then(|build_hasher| {
-                        Self::get_content_hash(build_hasher, meta_path.path()).ok()
-                    }),
+            let kind = if metadata.is_file() {
+                FileKind::File
+            } else if metadata.is_dir() {
+                FileKind::Folder
+            } else {
+                FileKind::Other
+            };
 
-                last_check: data_builder.now,
+            PathData {
+                mtime: FileTime::from_last_modification_time(metadata),
+                size: metadata.len(),
+                mode: Self::get_mode(metadata),
+                owner: Self::get_owner(metadata),
+                kind,
+                id: Self::get_id(metadata),
+                hash: match scanner.hasher {
+                    Some(ref hasher) if metadata.is_file() => hasher
+                        .hash_file(meta_path.path(), metadata.len())
+                        .ok()
+                        .flatten(),
+                    _ => None,
+                },
             }
         }
 
-        /// Get hash value for the data content in given file `path`.
-        fn get_content_hash(build_hasher: &RandomState, path: &Path) -> io::Result<u64> {
-            let mut hasher = build_hasher.build_hasher();
-            let mut file = File::open(path)?;
-            let mut buf = [0; 512];
+        #[cfg(unix)]
+        fn get_mode(metadata: &Metadata) -> u32 {
+            use std::os::unix::fs::PermissionsExt;
+            metadata.permissions().mode() & 0o7777
+        }
 
-            loop {
-                let n = match file.read(&mut buf) {
-                    Ok(0) => break,
-                    Ok(len) => len,
-                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
-                    Err(e) => return Err(e),
-                };
+        #[cfg(not(unix))]
+        fn get_mode(metadata: &Metadata) -> u32 {
+            metadata.permissions().readonly() as u32
+        }
 
-                hasher.write(&buf[..n]);
-            }
+        #[cfg(unix)]
+        fn get_owner(metadata: &Metadata) -> (u32, u32) {
+            use std::os::unix::fs::MetadataExt;
+            (metadata.uid(), metadata.gid())
+        }
 
-            Ok(hasher.finish())
+        #[cfg(not(unix))]
+        fn get_owner(_metadata: &Metadata) -> (u32, u32) {
+            (0, 0)
         }
 
-        /// Get [`Event`] by compare two optional [`PathData`].
-        fn compare_to_event<P>(
+        #[cfg(unix)]
+        fn get_id(metadata: &Metadata) -> Option<(u64, u64)> {
+            use std::os::unix::fs::MetadataExt;
+            Some((metadata.dev(), metadata.ino()))
+        }
+
+        #[cfg(not(unix))]
+        fn get_id(_metadata: &Metadata) -> Option<(u64, u64)> {
+            None
+        }
+
+        /// Get [`Event`]s by compare two optional [`PathData`].
+        ///
+        /// A change of the content is reported as one event, changes of the
+        /// permissions and the owner as one more each.
+        fn compare_to_events<P>(
             path: P,
             old: Option<&PathData>,
             new: Option<&PathData>,
-        ) -> Option<Event>
+        ) -> Vec<Event>
         where
             P: Into<PathBuf>,
         {
+            let mut kinds = Vec::new();
             match (old, new) {
+                (Some(old), Some(new)) if old.kind != new.kind => {
+                    kinds.push(old.kind.remove_event());
+                    kinds.push(new.kind.create_event());
+                }
                 (Some(old), Some(new)) => {
-                    if new.mtime > old.mtime {
-                        Some(EventKind::Modify(ModifyKind::Metadata(
-                            MetadataKind::WriteTime,
-                        )))
+                    if new.size != old.size {
+                        kinds.push(EventKind::Modify(ModifyKind::Data(DataChange::Size)));
                     } else if new.hash != old.hash {
-                        Some(EventKind::Modify(ModifyKind::Data(DataChange::Any)))
-                    } else {
-                        None
+                        kinds.push(EventKind::Modify(ModifyKind::Data(DataChange::Content)));
+                    } else if new.mtime != old.mtime {
+                        kinds.push(EventKind::Modify(ModifyKind::Metadata(
+                            MetadataKind::WriteTime,
+                        )));
+                    }
+                    if new.mode != old.mode {
+                        kinds.push(EventKind::Modify(ModifyKind::Metadata(
+                            MetadataKind::Permissions,
+                        )));
+                    }
+                    if new.owner != old.owner {
+                        kinds.push(EventKind::Modify(ModifyKind::Metadata(
+                            MetadataKind::Ownership,
+                        )));
                     }
                 }
-                (None, Some(_new)) => Some(EventKind::Create(CreateKind::Any)),
-                (Some(_old), None) => Some(EventKind::Remove(RemoveKind::Any)),
-                (None, None) => None,
+                (None, Some(new)) => kinds.push(new.kind.create_event()),
+                (Some(old), None) => kinds.push(old.kind.remove_event()),
+                (None, None) => {}
             }
-            .map(|event_kind| Event::new(event_kind).add_path(path.into()))
+
+            let path = path.into();
+            kinds
+                .into_iter()
+                .map(|kind| Event::new(kind).add_path(path.clone()))
+                .collect()
         }
     }
 
+    /// [`PathData`] saved between runs, see [`crate::Config::with_snapshot_file`].
+    #[derive(Debug)]
+    pub(crate) struct Snapshot {
+        file: PathBuf,
+
+        // whether the data of the watches has content hashes.
+        compare_contents: bool,
+
+        // saved watches not watched again yet.
+        watches: HashMap<PathBuf, SavedWatch>,
+    }
+
+    #[derive(Debug)]
+    pub(crate) struct SavedWatch {
+        is_recursive: bool,
+
+        // whether `all_path_data` has content hashes.
+        hashed: bool,
+        all_path_data: BTreeMap<PathBuf, PathData>,
+    }
+
+    impl Snapshot {
+        // format version in the last byte.
+        const MAGIC: &'static [u8] = b"notify-poll-snapshot\x01";
+
+        /// Read the snapshot saved to `file`, empty if there is none yet.
+        ///
+        /// An unreadable snapshot is returned empty along with its error.
+        pub(crate) fn load(file: PathBuf, compare_contents: bool) -> (Self, Option<io::Error>) {
+            let mut snapshot = Self {
+                file,
+                compare_contents,
+                watches: HashMap::new(),
+            };
+            let error = match snapshot.read() {
+                Ok(()) => None,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+                Err(e) => {
+                    snapshot.watches.clear();
+                    Some(e)
+                }
+            };
+            (snapshot, error)
+        }
+
+        fn read(&mut self) -> io::Result<()> {
+            let mut reader = BufReader::new(File::open(&self.file)?);
+            let mut magic = [0; Self::MAGIC.len()];
+            reader.read_exact(&mut magic)?;
+            if magic != Self::MAGIC {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "not a poll watcher snapshot",
+                ));
+            }
+            for _ in 0..read_u64(&mut reader)? {
+                let root = read_path(&mut reader)?;
+                let is_recursive = read_u64(&mut reader)? != 0;
+                let hashed = read_u64(&mut reader)? != 0;
+                let mut all_path_data = BTreeMap::new();
+                for _ in 0..read_u64(&mut reader)? {
+                    let path = read_path(&mut reader)?;
+                    let seconds = read_u64(&mut reader)? as i64;
+                    let nanos = read_u64(&mut reader)? as u32;
+                    let size = read_u64(&mut reader)?;
+                    let mode = read_u64(&mut reader)? as u32;
+                    let uid = read_u64(&mut reader)? as u32;
+                    let gid = read_u64(&mut reader)? as u32;
+                    let kind = match read_u64(&mut reader)? {
+                        0 => FileKind::File,
+                        1 => FileKind::Folder,
+                        _ => FileKind::Other,
+                    };
+                    let has_id = read_u64(&mut reader)? != 0;
+                    let dev = read_u64(&mut reader)?;
+                    let ino = read_u64(&mut reader)?;
+                    let has_hash = read_u64(&mut reader)? != 0;
+                    let hash = read_u64(&mut reader)?;
+                    let path_data = PathData {
+                        mtime: FileTime::from_unix_time(seconds, nanos),
+                        size,
+                        mode,
+                        owner: (uid, gid),
+                        kind,
+                        id: has_id.then(|| (dev, ino)),
+                        hash: has_hash.then(|| hash),
+                    };
+                    all_path_data.insert(path, path_data);
+                }
+                self.watches.insert(
+                    root,
+                    SavedWatch {
+                        is_recursive,
+                        hashed,
+                        all_path_data,
+                    },
+                );
+            }
+            Ok(())
+        }
+
+        pub(crate) fn file(&self) -> &Path {
+            &self.file
+        }
+
+        /// Take the saved data of `root`, if it was watched the same way.
+        pub(crate) fn take(&mut self, root: &Path, is_recursive: bool) -> Option<SavedWatch> {
+            match self.watches.remove(root) {
+                Some(saved) if saved.is_recursive == is_recursive => Some(saved),
+                _ => None,
+            }
+        }
+
+        /// Save `watches`, and the saved watches not watched again, replacing the file.
+        pub(crate) fn save(&self, watches: &HashMap<PathBuf, WatchData>) -> io::Result<()> {
+            let mut tmp = self.file.clone().into_os_string();
+            tmp.push(".tmp");
+            let mut writer = BufWriter::new(File::create(&tmp)?);
+
+            let saved = watches
+                .values()
+                .filter(|w| w.has_baseline)
+                .map(|w| {
+                    (
+                        &w.root,
+                        w.is_recursive,
+                        self.compare_contents,
+                        &w.all_path_data,
+                    )
+                })
+                .chain(
+                    self.watches
+                        .iter()
+                        .filter(|(root, _)| !watches.contains_key(*root))
+                        .map(|(root, saved)| {
+                            (root, saved.is_recursive, saved.hashed, &saved.all_path_data)
+                        }),
+                )
+                .filter(|(root, _, _, _)| path_bytes(root).is_some())
+                .collect::<Vec<_>>();
+
+            writer.write_all(Self::MAGIC)?;
+            write_u64(&mut writer, saved.len() as u64)?;
+            for (root, is_recursive, hashed, all_path_data) in saved {
+                write_path(&mut writer, root)?;
+                write_u64(&mut writer, is_recursive as u64)?;
+                write_u64(&mut writer, hashed as u64)?;
+                // paths that can't be written are reported as created next time.
+                let entries: Vec<_> = all_path_data
+                    .iter()
+                    .filter(|(path, _)| path_bytes(path).is_some())
+                    .collect();
+                write_u64(&mut writer, entries.len() as u64)?;
+                for (path, path_data) in entries {
+                    write_path(&mut writer, path)?;
+                    write_u64(&mut writer, path_data.mtime.unix_seconds() as u64)?;
+                    write_u64(&mut writer, u64::from(path_data.mtime.nanoseconds()))?;
+                    write_u64(&mut writer, path_data.size)?;
+                    write_u64(&mut writer, u64::from(path_data.mode))?;
+                    write_u64(&mut writer, u64::from(path_data.owner.0))?;
+                    write_u64(&mut writer, u64::from(path_data.owner.1))?;
+                    write_u64(&mut writer, path_data.kind as u64)?;
+                    let (dev, ino) = path_data.id.unwrap_or_default();
+                    write_u64(&mut writer, path_data.id.is_some() as u64)?;
+                    write_u64(&mut writer, dev)?;
+                    write_u64(&mut writer, ino)?;
+                    write_u64(&mut writer, path_data.hash.is_some() as u64)?;
+                    write_u64(&mut writer, path_data.hash.unwrap_or_default())?;
+                }
+            }
+            // on disk before the rename, so a crash can't leave an empty or partial snapshot
+            writer
+                .into_inner()
+                .map_err(|e| e.into_error())?
+                .sync_all()?;
+            fs::rename(&tmp, &self.file)?;
+            // and the rename itself is only durable once the directory is synced
+            #[cfg(unix)]
+            {
+                let dir = match self.file.parent() {
+                    Some(dir) if !dir.as_os_str().is_empty() => dir,
+                    _ => Path::new("."),
+                };
+                File::open(dir)?.sync_all()?;
+            }
+            Ok(())
+        }
+    }
+
+    fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+        let mut bytes = [0; 8];
+        reader.read_exact(&mut bytes)?;
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    fn write_u64(writer: &mut impl Write, value: u64) -> io::Result<()> {
+        writer.write_all(&value.to_le_bytes())
+    }
+
+    fn read_path(reader: &mut impl Read) -> io::Result<PathBuf> {
+        let len = read_u64(reader)?;
+        let mut bytes = Vec::new();
+        reader.take(len).read_to_end(&mut bytes)?;
+        if bytes.len() as u64 != len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        path_from_bytes(bytes)
+    }
+
+    fn write_path(writer: &mut impl Write, path: &Path) -> io::Result<()> {
+        let bytes = path_bytes(path).unwrap_or_default();
+        write_u64(writer, bytes.len() as u64)?;
+        writer.write_all(bytes)
+    }
+
+    #[cfg(unix)]
+    fn path_bytes(path: &Path) -> Option<&[u8]> {
+        use std::os::unix::ffi::OsStrExt;
+        Some(path.as_os_str().as_bytes())
+    }
+
+    #[cfg(not(unix))]
+    fn path_bytes(path: &Path) -> Option<&[u8]> {
+        path.to_str().map(str::as_bytes)
+    }
+
+    #[cfg(unix)]
+    fn path_from_bytes(bytes: Vec<u8>) -> io::Result<PathBuf> {
+        use std::os::unix::ffi::OsStringExt;
+        Ok(std::ffi::OsString::from_vec(bytes).into())
+    }
+
+    #[cfg(not(unix))]
+    fn path_from_bytes(bytes: Vec<u8>) -> io::Result<PathBuf> {
+        String::from_utf8(bytes)
+            .map(PathBuf::from)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
     /// Compose path and its metadata.
     ///
     /// This data structure designed for make sure path and its metadata can be
@@ -389,6 +1280,13 @@ mod data {
         {
             self.emit(Err(crate::Error::io(err.into()).add_path(path.into())))
         }
+
+        /// Emit error events.
+        fn emit_errors(&self, errors: Vec<crate::Error>) {
+            for error in errors {
+                self.emit(Err(error));
+            }
+        }
     }
 }
 
@@ -404,6 +1302,17 @@ pub struct PollWatcher {
     data_builder: Arc<Mutex<DataBuilder>>,
     want_to_stop: Arc<AtomicBool>,
     delay: Duration,
+    schedule: PollSchedule,
+    pending_watches: bool,
+    snapshot: Option<Arc<Mutex<Snapshot>>>,
+    snapshot_interval: Option<Duration>,
+    scan_complete_events: bool,
+    /// Asks the poll thread to scan now, with a sender to reply to once it is done
+    scan_requests: Sender<Option<Sender<()>>>,
+    /// Event kinds of the watched paths, see [`WatchOptions::with_event_kinds`]
+    filters: WatchFilters,
+    /// Threads helping with the scans, see [`Config::with_scan_workers`]
+    scan_pool: Option<Arc<ScanPool>>,
 }
 
 impl PollWatcher {
@@ -412,91 +1321,246 @@ impl PollWatcher {
         event_handler: F,
         config: Config,
     ) -> crate::Result<PollWatcher> {
-        let data_builder = DataBuilder::new(event_handler, config.compare_contents());
+        let (event_handler, filters, gitignore) = filtered_handler(event_handler, &config);
+        let scan_pool =
+            (config.scan_workers() > 1).then(|| ScanPool::start(config.scan_workers() - 1));
+        let data_builder = DataBuilder::new(
+            event_handler,
+            config
+                .compare_contents()
+                .then(|| config.hash_strategy().cloned().unwrap_or_default()),
+            IgnoreRules::either(config.ignore().cloned(), gitignore),
+            scan_pool.clone(),
+            config.symlink_policy(),
+        );
+        let (scan_requests, scan_requests_rx) = unbounded();
+        let snapshot = config.snapshot_file().map(|file| {
+            let (snapshot, error) = Snapshot::load(file.to_path_buf(), config.compare_contents());
+            if let Some(e) = error {
+                data_builder.emit_io_err(e, file);
+            }
+            Arc::new(Mutex::new(snapshot))
+        });
 
         let poll_watcher = PollWatcher {
             watches: Default::default(),
             data_builder: Arc::new(Mutex::new(data_builder)),
             want_to_stop: Arc::new(AtomicBool::new(false)),
             delay: config.poll_interval(),
+            schedule: config.poll_schedule(),
+            pending_watches: config.pending_watches(),
+            snapshot,
+            snapshot_interval: config.snapshot_interval(),
+            scan_complete_events: config.scan_complete_events(),
+            scan_requests,
+            filters,
+            scan_pool,
         };
 
-        poll_watcher.run();
+        poll_watcher.run(scan_requests_rx);
 
         Ok(poll_watcher)
     }
 
-    fn run(&self) {
+    fn run(&self, scan_requests: Receiver<Option<Sender<()>>>) {
         let watches = Arc::clone(&self.watches);
         let data_builder = Arc::clone(&self.data_builder);
         let want_to_stop = Arc::clone(&self.want_to_stop);
         let delay = self.delay;
+        let schedule = self.schedule;
+        let snapshot = self.snapshot.clone().zip(self.snapshot_interval);
+        let scan_complete_events = self.scan_complete_events;
 
         let _ = thread::Builder::new()
             .name("notify-rs poll loop".to_string())
             .spawn(move || {
+                let mut last_save = Instant::now();
+                let mut waiting: Vec<Sender<()>> = Vec::new();
+                // when the next scan is due with `PollSchedule::FixedRate`
+                let mut deadline = Instant::now();
+                // the wait of `PollSchedule::Adaptive`
+                let mut backoff = delay;
                 loop {
                     if want_to_stop.load(Ordering::SeqCst) {
                         break;
                     }
 
-                    // HINT: Make sure always lock in the same order to avoid deadlock.
+                    // Scan without holding the locks, so `watch` and `unwatch`
+                    // don't wait for it, then merge the results one by one.
                     //
                     // FIXME: inconsistent: some place mutex poison cause panic,
                     // some place just ignore.
-                    if let (Ok(mut watches), Ok(mut data_builder)) =
-                        (watches.lock(), data_builder.lock())
-                    {
+                    let scanner = data_builder.lock().ok().map(|mut data_builder| {
                         data_builder.update_timestamp();
+                        data_builder.scanner()
+                    });
+                    let roots: Vec<_> = match watches.lock() {
+                        Ok(watches) => watches
+                            .iter()
+                            .map(|(path, watch_data)| (path.clone(), watch_data.is_recursive()))
+                            .collect(),
+                        Err(_) => Vec::new(),
+                    };
+
+                    let mut changed = false;
+                    if let Some(scanner) = scanner {
+                        for (path, is_recursive) in roots {
+                            let scan = scanner.scan(&path, is_recursive);
+
+                            // HINT: Make sure always lock in the same order to avoid deadlock.
+                            if let (Ok(mut watches), Ok(data_builder)) =
+                                (watches.lock(), data_builder.lock())
+                            {
+                                // it may have been unwatched in the meantime.
+                                if let Some(watch_data) = watches.get_mut(&path) {
+                                    changed |= watch_data.merge_scan(&data_builder, scan);
+                                }
+                            }
+                        }
+                    }
 
-                        let vals = watches.values_mut();
-                        for watch_data in vals {
-                            watch_data.rescan(&mut data_builder);
+                    if scan_complete_events {
+                        if let Ok(data_builder) = data_builder.lock() {
+                            let event = Event::new(EventKind::Other).set_flag(Flag::ScanComplete);
+                            data_builder.emit_ok(event);
                         }
                     }
+                    for done in waiting.drain(..) {
+                        let _ = done.send(());
+                    }
 
-                    // QUESTION: `actual_delay == process_time + delay`. Is it intended to?
-                    //
-                    // If not, consider fix it to:
-                    //
-                    // ```rust
-                    // let still_need_to_delay = delay.checked_sub(data_builder.now.elapsed());
-                    // if let Some(delay) = still_need_to_delay {
-                    //     thread::sleep(delay);
-                    // }
-                    // ```
-                    thread::sleep(delay);
+                    if let Some((ref snapshot, interval)) = snapshot {
+                        if last_save.elapsed() >= interval {
+                            last_save = Instant::now();
+                            save_snapshot(&watches, &data_builder, snapshot);
+                        }
+                    }
+
+                    let wait = match schedule {
+                        PollSchedule::FixedDelay => delay,
+                        PollSchedule::FixedRate => {
+                            // scans that were due while this one ran are skipped
+                            deadline = (deadline + delay).max(Instant::now());
+                            deadline.saturating_duration_since(Instant::now())
+                        }
+                        PollSchedule::Adaptive { max_interval } => {
+                            let max_interval = max_interval.max(delay);
+                            backoff = if changed {
+                                delay
+                            } else {
+                                backoff
+                                    .checked_mul(2)
+                                    .map_or(max_interval, |longer| longer.min(max_interval))
+                            };
+                            backoff
+                        }
+                    };
+
+                    // Requests for a scan cut the wait short. A request coming in
+                    // during a scan gets the next one, which sees all changes made
+                    // before it. Dropping the watcher cuts it short as well.
+                    if let Ok(request) = scan_requests.recv_timeout(wait) {
+                        waiting.extend(request);
+                        waiting.extend(scan_requests.try_iter().flatten());
+                        deadline = Instant::now();
+                    }
                 }
             });
     }
 
     /// Watch a path location.
     ///
-    /// QUESTION: this function never return an Error, is it as intend?
-    /// Please also consider the IO Error event problem.
-    fn watch_inner(&mut self, path: &Path, recursive_mode: RecursiveMode) {
-        // HINT: Make sure always lock in the same order to avoid deadlock.
-        //
-        // FIXME: inconsistent: some place mutex poison cause panic, some place just ignore.
-        if let (Ok(mut watches), Ok(mut data_builder)) =
-            (self.watches.lock(), self.data_builder.lock())
-        {
+    /// Fails if the metadata of `path` can't be read, unless it is missing and
+    /// pending watches are allowed.
+    fn watch_inner(&mut self, path: &Path, recursive_mode: RecursiveMode) -> crate::Result<()> {
+        let scanner = {
+            let mut data_builder = self.data_builder.lock()?;
             data_builder.update_timestamp();
+            data_builder.scanner()
+        };
 
-            let watch_data =
-                data_builder.build_watch_data(path.to_path_buf(), recursive_mode.is_recursive());
+        WatchData::check_root(path, self.pending_watches)
+            .map_err(|e| crate::Error::io(e).add_path(path.to_path_buf()))?;
 
-            // if create watch_data successful, add it to watching list.
-            if let Some(watch_data) = watch_data {
-                watches.insert(path.to_path_buf(), watch_data);
-            }
-        }
+        // scanned without the locks, like the rescans of the poll thread.
+        let is_recursive = recursive_mode.is_recursive();
+        let scan = scanner.scan(path, is_recursive);
+        let saved = self.snapshot.as_ref().and_then(|snapshot| {
+            let mut snapshot = snapshot.lock().unwrap_or_else(|e| e.into_inner());
+            snapshot.take(path, is_recursive)
+        });
+
+        // HINT: Make sure always lock in the same order to avoid deadlock.
+        let mut watches = self.watches.lock()?;
+        let data_builder = self.data_builder.lock()?;
+        let path = path.to_path_buf();
+        let watch_data = match saved {
+            Some(saved) => WatchData::from_snapshot(&data_builder, path.clone(), saved, scan),
+            None => WatchData::from_scan(&data_builder, path.clone(), is_recursive, scan),
+        };
+        watches.insert(path, watch_data);
+        Ok(())
+    }
+
+    /// Watch `path` like [`Watcher::watch_with`], leaving its first scan to the poll thread.
+    ///
+    /// Returns without reading the tree below `path`, for callers that can't wait for it.
+    /// Changes are reported from that first scan on, which is requested right away.
+    #[cfg(target_os = "linux")]
+    pub(crate) fn watch_deferred(
+        &mut self,
+        path: &Path,
+        options: WatchOptions,
+    ) -> crate::Result<()> {
+        let filters = self.filters.clone();
+        filters.add(path.to_path_buf(), options, || {
+            let is_recursive = options.recursive_mode().is_recursive();
+            let watch_data = WatchData::deferred(path.to_path_buf(), is_recursive);
+            self.watches.lock()?.insert(path.to_path_buf(), watch_data);
+            self.poll_now()
+        })
+    }
+
+    /// Scan the watched paths now, instead of waiting for the poll interval to pass.
+    ///
+    /// Returns right away, see [PollWatcher::poll_now_blocking] to wait for the scan.
+    pub fn poll_now(&self) -> crate::Result<()> {
+        self.scan_requests.send(None)?;
+        Ok(())
+    }
+
+    /// Scan the watched paths now, and wait until the scan is done.
+    ///
+    /// All changes made before the call are reported before it returns. A scan already running
+    /// is finished first.
+    pub fn poll_now_blocking(&self) -> crate::Result<()> {
+        let (tx, rx) = unbounded();
+        self.scan_requests.send(Some(tx))?;
+        rx.recv()?;
+        Ok(())
+    }
+
+    /// Save the [snapshot file](Config::with_snapshot_file) now.
+    ///
+    /// Fails if no snapshot file is configured or it can't be written.
+    pub fn save_snapshot(&self) -> crate::Result<()> {
+        let snapshot = self
+            .snapshot
+            .as_ref()
+            .ok_or_else(|| crate::Error::generic("no snapshot file configured"))?;
+        // HINT: Make sure always lock in the same order to avoid deadlock.
+        let watches = self.watches.lock().unwrap_or_else(|e| e.into_inner());
+        let snapshot = snapshot.lock().unwrap_or_else(|e| e.into_inner());
+        snapshot
+            .save(&watches)
+            .map_err(|e| crate::Error::io(e).add_path(snapshot.file().to_path_buf()))
     }
 
     /// Unwatch a path.
     ///
     /// Return `Err(_)` if given path has't be monitored.
     fn unwatch_inner(&mut self, path: &Path) -> crate::Result<()> {
+        self.filters.remove(path);
         // FIXME: inconsistent: some place mutex poison cause panic, some place just ignore.
         self.watches
             .lock()
@@ -514,15 +1578,27 @@ impl Watcher for PollWatcher {
     }
 
     fn watch(&mut self, path: &Path, recursive_mode: RecursiveMode) -> crate::Result<()> {
-        self.watch_inner(path, recursive_mode);
+        self.watch_with(path, WatchOptions::new(recursive_mode))
+    }
 
-        Ok(())
+    fn watch_with(&mut self, path: &Path, options: WatchOptions) -> crate::Result<()> {
+        let filters = self.filters.clone();
+        filters.add(path.to_path_buf(), options, || {
+            self.watch_inner(path, options.recursive_mode())
+        })
     }
 
     fn unwatch(&mut self, path: &Path) -> crate::Result<()> {
         self.unwatch_inner(path)
     }
 
+    fn watches(&self) -> crate::Result<Vec<WatchInfo>> {
+        let watches = self.watches.lock()?;
+        let mut infos: Vec<_> = watches.values().map(WatchData::info).collect();
+        infos.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(infos)
+    }
+
     fn kind() -> crate::WatcherKind {
         crate::WatcherKind::PollWatcher
     }
@@ -531,6 +1607,27 @@ impl Watcher for PollWatcher {
 impl Drop for PollWatcher {
     fn drop(&mut self) {
         self.want_to_stop.store(true, Ordering::Relaxed);
+        if let Some(ref snapshot) = self.snapshot {
+            save_snapshot(&self.watches, &self.data_builder, snapshot);
+        }
+        if let Some(ref scan_pool) = self.scan_pool {
+            scan_pool.stop();
+        }
+    }
+}
+
+/// Save `snapshot`, reporting a failure as an error event.
+fn save_snapshot(
+    watches: &Mutex<HashMap<PathBuf, WatchData>>,
+    data_builder: &Mutex<DataBuilder>,
+    snapshot: &Mutex<Snapshot>,
+) {
+    // HINT: Make sure always lock in the same order to avoid deadlock.
+    let watches = watches.lock().unwrap_or_else(|e| e.into_inner());
+    let data_builder = data_builder.lock().unwrap_or_else(|e| e.into_inner());
+    let snapshot = snapshot.lock().unwrap_or_else(|e| e.into_inner());
+    if let Err(e) = snapshot.save(&watches) {
+        data_builder.emit_io_err(e, snapshot.file());
     }
 }
 
@@ -539,3 +1636,593 @@ fn poll_watcher_is_send_and_sync() {
     fn check<T: Send + Sync>() {}
     check::<PollWatcher>();
 }
+
+#[test]
+fn poll_watcher_skips_ignored_paths() {
+    use std::{fs, sync::mpsc, time::Duration};
+
+    let dir = tempfile::tempdir().unwrap();
+    fs::create_dir(dir.path().join("target")).unwrap();
+
+    let (tx, rx) = mpsc::channel();
+    let config = Config::default()
+        .with_poll_interval(Duration::from_millis(10))
+        .with_ignore(crate::IgnoreRules::patterns(["target", "*.tmp"]));
+    let mut watcher = PollWatcher::new(tx, config).unwrap();
+    watcher.watch(dir.path(), RecursiveMode::Recursive).unwrap();
+
+    fs::write(dir.path().join("target").join("out"), "").unwrap();
+    fs::write(dir.path().join("scratch.tmp"), "").unwrap();
+    fs::write(dir.path().join("kept"), "").unwrap();
+
+    let kept = dir.path().join("kept");
+    loop {
+        let event = rx.recv_timeout(Duration::from_secs(5)).unwrap().unwrap();
+        // the root itself may be reported as modified as well
+        assert!(event.paths.iter().all(|p| p == &kept || p == dir.path()));
+        if event.paths == [kept.clone()] {
+            break;
+        }
+    }
+    while let Ok(event) = rx.recv_timeout(Duration::from_millis(100)) {
+        let event = event.unwrap();
+        assert!(event.paths.iter().all(|p| p == &kept || p == dir.path()));
+    }
+}
+
+#[test]
+fn scan_pool_outlives_scans() {
+    use crate::SymlinkPolicy;
+    use std::fs;
+
+    let dir = tempfile::tempdir().unwrap();
+    for sub in ["a/b/c", "d/e", "f"] {
+        fs::create_dir_all(dir.path().join(sub)).unwrap();
+        fs::write(dir.path().join(sub).join("file"), "").unwrap();
+    }
+    let pool = ScanPool::start(3);
+    let data_builder = DataBuilder::new(
+        |_: crate::Result<Event>| {},
+        None,
+        None,
+        Some(Arc::clone(&pool)),
+        SymlinkPolicy::DontFollow,
+    );
+    let count = || {
+        let scan = data_builder.scanner().scan(dir.path(), true);
+        WatchData::from_scan(&data_builder, dir.path().to_path_buf(), true, scan)
+            .info()
+            .count
+    };
+    // the root, six directories and three files
+    for _ in 0..10 {
+        assert_eq!(count(), 10);
+    }
+
+    // scans go on without the pool
+    pool.stop();
+    assert_eq!(count(), 10);
+}
+
+#[test]
+fn poll_watcher_scans_with_workers() {
+    use std::{collections::HashSet, fs, sync::mpsc, time::Duration};
+
+    let dir = tempfile::tempdir().unwrap();
+    for sub in ["a/b/c", "d/e", "f"] {
+        fs::create_dir_all(dir.path().join(sub)).unwrap();
+    }
+    fs::write(dir.path().join("d/e/old"), "").unwrap();
+    // followed once, not forever
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(dir.path(), dir.path().join("a/b/root")).unwrap();
+
+    let (tx, rx) = mpsc::channel();
+    let config = Config::default()
+        .with_poll_interval(Duration::from_millis(10))
+        .with_scan_workers(4);
+    let mut watcher = PollWatcher::new(tx, config).unwrap();
+    watcher.watch(dir.path(), RecursiveMode::Recursive).unwrap();
+
+    let new = dir.path().join("a/b/c/new");
+    let old = dir.path().join("d/e/old");
+    fs::write(&new, "").unwrap();
+    fs::remove_file(&old).unwrap();
+
+    let mut created = HashSet::new();
+    let mut removed = HashSet::new();
+    while created.is_empty() || removed.is_empty() {
+        let event = rx.recv_timeout(Duration::from_secs(5)).unwrap().unwrap();
+        if event.kind.is_create() {
+            created.extend(event.paths);
+        } else if event.kind.is_remove() {
+            removed.extend(event.paths);
+        }
+    }
+    assert_eq!(created, HashSet::from([new]));
+    assert_eq!(removed, HashSet::from([old]));
+}
+
+#[test]
+fn poll_watcher_watches_while_scanning() {
+    use std::{sync::mpsc, time::Duration};
+
+    let slow = tempfile::tempdir().unwrap();
+    let other = tempfile::tempdir().unwrap();
+
+    // holds up the next scan of `slow` once armed
+    let armed = Arc::new(AtomicBool::new(false));
+    let (entered_tx, entered_rx) = mpsc::channel();
+    let (gate_tx, gate_rx) = mpsc::channel::<()>();
+    let gate = Mutex::new((entered_tx, gate_rx));
+    let ignore = {
+        let armed = Arc::clone(&armed);
+        let slow = slow.path().to_path_buf();
+        IgnoreRules::new(move |path| {
+            if path.starts_with(&slow) && armed.swap(false, Ordering::SeqCst) {
+                let gate = gate.lock().unwrap();
+                let _ = gate.0.send(());
+                let _ = gate.1.recv();
+            }
+            false
+        })
+    };
+    std::fs::write(slow.path().join("file"), "").unwrap();
+
+    let (tx, _rx) = mpsc::channel();
+    let config = Config::default()
+        .with_poll_interval(Duration::from_millis(10))
+        .with_ignore(ignore);
+    let mut watcher = PollWatcher::new(tx, config).unwrap();
+    watcher
+        .watch(slow.path(), RecursiveMode::Recursive)
+        .unwrap();
+    armed.store(true, Ordering::SeqCst);
+    entered_rx.recv_timeout(Duration::from_secs(5)).unwrap();
+
+    // the poll thread is stuck scanning `slow`
+    let (done_tx, done_rx) = mpsc::channel();
+    let other_path = other.path().to_path_buf();
+    let handle = thread::spawn(move || {
+        watcher
+            .watch(&other_path, RecursiveMode::Recursive)
+            .unwrap();
+        watcher.unwatch(&other_path).unwrap();
+        done_tx.send(()).unwrap();
+        watcher
+    });
+    let done = done_rx.recv_timeout(Duration::from_secs(5));
+    gate_tx.send(()).unwrap();
+    done.unwrap();
+    handle.join().unwrap();
+}
+
+#[test]
+fn poll_watcher_reports_changes_since_snapshot() {
+    use crate::event::{CreateKind, DataChange, EventKind, ModifyKind, RemoveKind};
+    use filetime::FileTime;
+    use std::{fs, sync::mpsc};
+
+    let dir = tempfile::tempdir().unwrap();
+    let state = tempfile::tempdir().unwrap();
+    let root = dir.path().join("root");
+    fs::create_dir(&root).unwrap();
+    fs::write(root.join("kept"), "kept").unwrap();
+    fs::write(root.join("changed"), "old").unwrap();
+    fs::write(root.join("gone"), "").unwrap();
+
+    let config = Config::default()
+        .with_poll_interval(Duration::from_secs(3600))
+        .with_snapshot_file(state.path().join("snapshot"));
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = PollWatcher::new(tx.clone(), config.clone()).unwrap();
+    watcher.watch(&root, RecursiveMode::Recursive).unwrap();
+    drop(watcher);
+    assert!(rx.try_recv().is_err());
+
+    // while not running, keeping the modification time
+    let mtime = FileTime::from_last_modification_time(&fs::metadata(root.join("changed")).unwrap());
+    fs::write(root.join("changed"), "longer").unwrap();
+    filetime::set_file_mtime(root.join("changed"), mtime).unwrap();
+    fs::remove_file(root.join("gone")).unwrap();
+    fs::write(root.join("new"), "").unwrap();
+
+    let mut watcher = PollWatcher::new(tx, config).unwrap();
+    watcher.watch(&root, RecursiveMode::Recursive).unwrap();
+    let mut events: Vec<_> = rx
+        .try_iter()
+        .map(|event| event.unwrap())
+        .filter(|event| event.paths != [root.clone()])
+        .map(|event| (event.paths, event.kind))
+        .collect();
+    events.sort_by(|a, b| a.0.cmp(&b.0));
+    assert_eq!(
+        events,
+        [
+            (
+                vec![root.join("changed")],
+                EventKind::Modify(ModifyKind::Data(DataChange::Size))
+            ),
+            (vec![root.join("gone")], EventKind::Remove(RemoveKind::File)),
+            (vec![root.join("new")], EventKind::Create(CreateKind::File)),
+        ]
+    );
+}
+
+#[test]
+fn poll_watcher_classifies_changes() {
+    use crate::event::{CreateKind, DataChange, EventKind, MetadataKind, ModifyKind};
+    use std::{fs, sync::mpsc};
+
+    let dir = tempfile::tempdir().unwrap();
+    let (tx, rx) = mpsc::channel();
+    let config = Config::default()
+        .with_poll_interval(Duration::from_secs(3600))
+        .with_compare_contents(true);
+    let mut watcher = PollWatcher::new(tx, config).unwrap();
+    watcher.watch(dir.path(), RecursiveMode::Recursive).unwrap();
+    let poll = || -> Vec<_> {
+        watcher.poll_now_blocking().unwrap();
+        rx.try_iter()
+            .map(|event| event.unwrap())
+            .filter(|event| event.paths != [dir.path().to_path_buf()])
+            .map(|event| (event.paths, event.kind))
+            .collect()
+    };
+    // past the scan of the poll thread starting, which could see half written files
+    assert_eq!(poll(), []);
+
+    let file = dir.path().join("file");
+    let folder = dir.path().join("folder");
+    fs::write(&file, "old").unwrap();
+    fs::create_dir(&folder).unwrap();
+    let mut events = poll();
+    events.sort_by(|a, b| a.0.cmp(&b.0));
+    assert_eq!(
+        events,
+        [
+            (vec![file.clone()], EventKind::Create(CreateKind::File)),
+            (vec![folder], EventKind::Create(CreateKind::Folder)),
+        ]
+    );
+
+    fs::write(&file, "new").unwrap();
+    assert_eq!(
+        poll(),
+        [(
+            vec![file.clone()],
+            EventKind::Modify(ModifyKind::Data(DataChange::Content))
+        )]
+    );
+
+    let mut permissions = fs::metadata(&file).unwrap().permissions();
+    permissions.set_readonly(true);
+    fs::set_permissions(&file, permissions).unwrap();
+    assert_eq!(
+        poll(),
+        [(
+            vec![file],
+            EventKind::Modify(ModifyKind::Metadata(MetadataKind::Permissions))
+        )]
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn poll_watcher_pairs_renames() {
+    use crate::event::{EventKind, ModifyKind, RenameMode};
+    use std::{fs, sync::mpsc};
+
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().to_path_buf();
+    fs::write(root.join("file"), "file").unwrap();
+    fs::create_dir_all(root.join("dir/sub")).unwrap();
+    fs::write(root.join("dir/sub/inner"), "inner").unwrap();
+
+    let (tx, rx) = mpsc::channel();
+    let config = Config::default().with_poll_interval(Duration::from_secs(3600));
+    let mut watcher = PollWatcher::new(tx, config).unwrap();
+    watcher.watch(&root, RecursiveMode::Recursive).unwrap();
+
+    fs::rename(root.join("file"), root.join("renamed")).unwrap();
+    fs::rename(root.join("dir"), root.join("moved")).unwrap();
+    watcher.poll_now_blocking().unwrap();
+    let mut events: Vec<_> = rx
+        .try_iter()
+        .map(|event| event.unwrap())
+        .filter(|event| event.paths != [root.clone()])
+        .map(|event| (event.kind, event.paths))
+        .collect();
+    events.sort_by(|a, b| a.1.cmp(&b.1));
+    let rename = EventKind::Modify(ModifyKind::Name(RenameMode::Both));
+    assert_eq!(
+        events,
+        [
+            (rename.clone(), vec![root.join("dir"), root.join("moved")]),
+            (rename, vec![root.join("file"), root.join("renamed")]),
+        ]
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn poll_watcher_follows_symlinks_by_policy() {
+    use crate::SymlinkPolicy;
+    use std::{collections::HashSet, fs, os::unix::fs::symlink, sync::mpsc};
+
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().join("root");
+    let outside = dir.path().join("outside");
+    fs::create_dir_all(root.join("inside")).unwrap();
+    fs::create_dir(&outside).unwrap();
+    symlink(root.join("inside"), root.join("to_inside")).unwrap();
+    symlink(&outside, root.join("to_outside")).unwrap();
+    symlink(&root, root.join("inside/loop")).unwrap();
+
+    let cases = [
+        (SymlinkPolicy::DontFollow, vec!["inside/new"]),
+        (
+            SymlinkPolicy::FollowWithinRoot,
+            vec!["inside/new", "to_inside/new"],
+        ),
+        (
+            SymlinkPolicy::Follow,
+            vec!["inside/new", "to_inside/new", "to_outside/new"],
+        ),
+    ];
+    for (policy, expected) in cases {
+        let (tx, rx) = mpsc::channel();
+        let config = Config::default()
+            .with_poll_interval(Duration::from_secs(3600))
+            .with_symlink_policy(policy);
+        let mut watcher = PollWatcher::new(tx, config).unwrap();
+        watcher.watch(&root, RecursiveMode::Recursive).unwrap();
+        fs::write(root.join("inside/new"), "").unwrap();
+        fs::write(outside.join("new"), "").unwrap();
+        watcher.poll_now_blocking().unwrap();
+
+        let created: HashSet<_> = rx
+            .try_iter()
+            .map(|event| event.unwrap())
+            .filter(|event| event.kind.is_create())
+            .flat_map(|event| event.paths)
+            .collect();
+        let expected: HashSet<_> = expected.iter().map(|path| root.join(path)).collect();
+        assert_eq!(created, expected, "{:?}", policy);
+        fs::remove_file(root.join("inside/new")).unwrap();
+        fs::remove_file(outside.join("new")).unwrap();
+    }
+}
+
+#[test]
+fn poll_watcher_backs_off_while_idle() {
+    use std::sync::mpsc;
+
+    let scans = |schedule| {
+        let dir = tempfile::tempdir().unwrap();
+        let (tx, rx) = mpsc::channel();
+        let config = Config::default()
+            .with_poll_interval(Duration::from_millis(10))
+            .with_poll_schedule(schedule)
+            .with_scan_complete_events(true);
+        let mut watcher = PollWatcher::new(tx, config).unwrap();
+        watcher.watch(dir.path(), RecursiveMode::Recursive).unwrap();
+        thread::sleep(Duration::from_millis(500));
+        drop(watcher);
+        rx.try_iter()
+            .filter(|event| event.as_ref().unwrap().flag() == Some(Flag::ScanComplete))
+            .count()
+    };
+
+    // waits of 20, 40, 80 and 160 ms
+    let adaptive = scans(PollSchedule::Adaptive {
+        max_interval: Duration::from_secs(3600),
+    });
+    assert!(adaptive <= 6, "{} scans", adaptive);
+    assert!(scans(PollSchedule::FixedRate) >= 10);
+}
+
+#[test]
+fn poll_watcher_lists_watches() {
+    use crate::{WatchInfo, WatchStatus};
+    use std::fs;
+
+    let dir = tempfile::tempdir().unwrap();
+    let tree = dir.path().join("tree");
+    let missing = dir.path().join("missing");
+    fs::create_dir_all(tree.join("sub")).unwrap();
+    fs::write(tree.join("sub/file"), "").unwrap();
+
+    let config = Config::default()
+        .with_poll_interval(Duration::from_secs(3600))
+        .with_pending_watches(true);
+    let mut watcher = PollWatcher::new(|_: crate::Result<Event>| {}, config).unwrap();
+    watcher.watch(&tree, RecursiveMode::Recursive).unwrap();
+    watcher
+        .watch(&missing, RecursiveMode::NonRecursive)
+        .unwrap();
+    assert_eq!(
+        watcher.watches().unwrap(),
+        [
+            WatchInfo {
+                path: missing,
+                recursive_mode: RecursiveMode::NonRecursive,
+                count: 0,
+                status: WatchStatus::Pending,
+            },
+            WatchInfo {
+                path: tree,
+                recursive_mode: RecursiveMode::Recursive,
+                count: 3,
+                status: WatchStatus::Active,
+            },
+        ]
+    );
+}
+
+#[test]
+fn poll_watcher_watches_pending_paths() {
+    use std::{fs, sync::mpsc};
+
+    let dir = tempfile::tempdir().unwrap();
+    let target = dir.path().join("a").join("b");
+    let config = Config::default().with_poll_interval(Duration::from_secs(3600));
+
+    // without pending watches, a missing path is not watched
+    let (tx, _rx) = mpsc::channel();
+    let mut watcher = PollWatcher::new(tx, config.clone()).unwrap();
+    assert!(watcher.watch(&target, RecursiveMode::Recursive).is_err());
+    assert!(watcher.watches().unwrap().is_empty());
+    assert!(watcher.filters.lock().is_empty());
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = PollWatcher::new(tx, config.with_pending_watches(true)).unwrap();
+    watcher.watch(&target, RecursiveMode::Recursive).unwrap();
+    watcher.poll_now_blocking().unwrap();
+    assert_eq!(rx.try_iter().count(), 0);
+
+    fs::create_dir_all(&target).unwrap();
+    watcher.poll_now_blocking().unwrap();
+    assert!(rx.try_iter().any(|event| event.unwrap().kind.is_create()));
+}
+
+#[test]
+#[cfg(unix)]
+fn poll_watcher_tells_new_files_from_renames() {
+    use crate::event::{CreateKind, EventKind, RemoveKind};
+    use filetime::FileTime;
+    use std::{fs, sync::mpsc};
+
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().to_path_buf();
+    fs::write(root.join("gone"), "gone").unwrap();
+    let mtime = FileTime::from_last_modification_time(&fs::metadata(root.join("gone")).unwrap());
+
+    let (tx, rx) = mpsc::channel();
+    let config = Config::default().with_poll_interval(Duration::from_secs(3600));
+    let mut watcher = PollWatcher::new(tx, config).unwrap();
+    watcher.watch(&root, RecursiveMode::Recursive).unwrap();
+
+    // likely to get the inode of `gone`, even its modification time
+    fs::remove_file(root.join("gone")).unwrap();
+    fs::write(root.join("new"), "new file").unwrap();
+    filetime::set_file_mtime(root.join("new"), mtime).unwrap();
+    watcher.poll_now_blocking().unwrap();
+    let mut events: Vec<_> = rx
+        .try_iter()
+        .map(|event| event.unwrap())
+        .filter(|event| event.paths != [root.clone()])
+        .map(|event| (event.kind, event.paths))
+        .collect();
+    events.sort_by(|a, b| a.1.cmp(&b.1));
+    assert_eq!(
+        events,
+        [
+            (EventKind::Remove(RemoveKind::File), vec![root.join("gone")]),
+            (EventKind::Create(CreateKind::File), vec![root.join("new")]),
+        ]
+    );
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn poll_watcher_scans_deferred_watches_on_its_thread() {
+    use std::{fs, sync::mpsc};
+
+    let dir = tempfile::tempdir().unwrap();
+    fs::create_dir(dir.path().join("sub")).unwrap();
+    fs::write(dir.path().join("sub/old"), "").unwrap();
+    let (tx, rx) = mpsc::channel();
+    let config = Config::default().with_poll_interval(Duration::from_secs(3600));
+    let mut watcher = PollWatcher::new(tx, config).unwrap();
+    let options = WatchOptions::new(RecursiveMode::Recursive);
+    watcher.watch_deferred(dir.path(), options).unwrap();
+    watcher.poll_now_blocking().unwrap();
+    assert_eq!(watcher.watches().unwrap()[0].count, 3);
+
+    // the first scan only sets the data changes are reported against
+    let new = dir.path().join("sub/new");
+    fs::write(&new, "").unwrap();
+    watcher.poll_now_blocking().unwrap();
+    let created: Vec<_> = rx
+        .try_iter()
+        .map(|event| event.unwrap())
+        .filter(|event| event.kind.is_create())
+        .flat_map(|event| event.paths)
+        .collect();
+    assert_eq!(created, [new]);
+}
+
+#[test]
+fn poll_watcher_polls_on_demand() {
+    use std::{fs, sync::mpsc};
+
+    let dir = tempfile::tempdir().unwrap();
+    let (tx, rx) = mpsc::channel();
+    let config = Config::default()
+        .with_poll_interval(Duration::from_secs(3600))
+        .with_scan_complete_events(true);
+    let mut watcher = PollWatcher::new(tx, config).unwrap();
+    watcher.watch(dir.path(), RecursiveMode::Recursive).unwrap();
+    // the scan right after starting
+    let event = rx.recv_timeout(Duration::from_secs(5)).unwrap().unwrap();
+    assert_eq!(event.flag(), Some(Flag::ScanComplete));
+
+    let file = dir.path().join("file");
+    fs::write(&file, "").unwrap();
+    watcher.poll_now_blocking().unwrap();
+    let events: Vec<_> = rx.try_iter().map(|event| event.unwrap()).collect();
+    let (marker, changes) = events.split_last().unwrap();
+    assert_eq!(marker.flag(), Some(Flag::ScanComplete));
+    assert!(marker.paths.is_empty());
+    assert!(changes
+        .iter()
+        .any(|event| event.kind.is_create() && event.paths == [file.clone()]));
+
+    fs::remove_file(&file).unwrap();
+    watcher.poll_now().unwrap();
+    // the directory may be reported as modified first
+    loop {
+        let event = rx.recv_timeout(Duration::from_secs(5)).unwrap().unwrap();
+        assert_ne!(event.flag(), Some(Flag::ScanComplete));
+        if event.kind.is_remove() {
+            assert_eq!(event.paths, [file]);
+            break;
+        }
+    }
+}
+
+#[test]
+fn poll_watcher_filters_event_kinds() {
+    use crate::event::{CreateKind, EventKind};
+    use crate::EventKindMask;
+    use std::{fs, sync::mpsc};
+
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("old"), "old").unwrap();
+    fs::write(dir.path().join("gone"), "").unwrap();
+
+    let (tx, rx) = mpsc::channel();
+    let config = Config::default().with_poll_interval(Duration::from_secs(3600));
+    let mut watcher = PollWatcher::new(tx, config).unwrap();
+    let options =
+        WatchOptions::new(RecursiveMode::Recursive).with_event_kinds(EventKindMask::CREATE);
+    watcher.watch_with(dir.path(), options).unwrap();
+
+    fs::write(dir.path().join("old"), "changed").unwrap();
+    fs::write(dir.path().join("new"), "").unwrap();
+    fs::remove_file(dir.path().join("gone")).unwrap();
+    watcher.poll_now_blocking().unwrap();
+    let events: Vec<_> = rx
+        .try_iter()
+        .map(|event| event.unwrap())
+        .map(|event| (event.paths, event.kind))
+        .collect();
+    assert_eq!(
+        events,
+        [(
+            vec![dir.path().join("new")],
+            EventKind::Create(CreateKind::File)
+        )]
+    );
+}
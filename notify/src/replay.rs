@@ -0,0 +1,413 @@
+//! Recording events and playing them back, requires the `replay` feature
+//!
+//! A [`RecordingHandler`] writes the events and errors passing through it to a [JSON Lines] file,
+//! each with the time since the recording started. A [`ReplayWatcher`] plays such a file back, so
+//! a sequence of events seen in production can become a regression test.
+//!
+//! ```no_run
+//! use notify::replay::{RecordingHandler, ReplayPace, ReplayWatcher};
+//! use notify::{Config, Event, RecommendedWatcher, RecursiveMode, Result, Watcher};
+//! use std::path::Path;
+//! use std::sync::mpsc;
+//!
+//! fn main() -> Result<()> {
+//!     // record what a watcher sees
+//!     let (tx, _rx) = mpsc::channel::<Result<Event>>();
+//!     let handler = RecordingHandler::create("events.jsonl", tx)?;
+//!     let mut watcher = RecommendedWatcher::new(handler, Config::default())?;
+//!     watcher.watch(Path::new("."), RecursiveMode::Recursive)?;
+//!     # drop(watcher);
+//!
+//!     // and play it back later
+//!     let (tx, rx) = mpsc::channel::<Result<Event>>();
+//!     let config = Config::default()
+//!         .with_replay_file("events.jsonl")
+//!         .with_replay_pace(ReplayPace::AsFastAsPossible);
+//!     let mut watcher = ReplayWatcher::new(tx, config)?;
+//!     watcher.watch(Path::new("."), RecursiveMode::Recursive)?;
+//!     // the channel disconnects at the end of the recording
+//!     for event in rx {
+//!         println!("{:?}", event);
+//!     }
+//!     Ok(())
+//! }
+//! ```
+//!
+//! [JSON Lines]: https://jsonlines.org
+
+use crate::filter::WatchFilters;
+use crate::{
+    Config, Error, ErrorKind, Event, EventHandler, RecursiveMode, Result, WatchInfo, WatchOptions,
+    Watcher,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How a [`ReplayWatcher`] paces the recorded events, see [`Config::with_replay_pace`]
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub enum ReplayPace {
+    /// As far apart as they were recorded
+    RealTime,
+
+    /// Right after each other
+    AsFastAsPossible,
+}
+
+impl Default for ReplayPace {
+    fn default() -> Self {
+        ReplayPace::RealTime
+    }
+}
+
+/// A line of a recording, with a borrowed event when writing
+#[derive(Serialize, Deserialize)]
+struct Record<E> {
+    /// Seconds since the recording started
+    time: f64,
+    #[serde(flatten)]
+    entry: Entry<E>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum Entry<E> {
+    Event(E),
+    Error(RecordedError),
+}
+
+/// An [`Error`], with what it takes to restore it
+#[derive(Serialize, Deserialize)]
+struct RecordedError {
+    kind: RecordedErrorKind,
+    /// Description of generic, I/O and configuration errors
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    paths: Vec<PathBuf>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum RecordedErrorKind {
+    Generic,
+    Io,
+    PathNotFound,
+    WatchNotFound,
+    InvalidConfig,
+    MaxFilesWatch,
+}
+
+impl RecordedError {
+    fn new(error: &Error) -> Self {
+        let (kind, message) = match error.kind {
+            ErrorKind::Generic(ref message) => (RecordedErrorKind::Generic, Some(message.clone())),
+            ErrorKind::Io(ref err) => (RecordedErrorKind::Io, Some(err.to_string())),
+            ErrorKind::PathNotFound => (RecordedErrorKind::PathNotFound, None),
+            ErrorKind::WatchNotFound => (RecordedErrorKind::WatchNotFound, None),
+            ErrorKind::InvalidConfig(ref config) => (
+                RecordedErrorKind::InvalidConfig,
+                Some(format!("{:?}", config)),
+            ),
+            ErrorKind::MaxFilesWatch => (RecordedErrorKind::MaxFilesWatch, None),
+        };
+        RecordedError {
+            kind,
+            message,
+            paths: error.paths.clone(),
+        }
+    }
+
+    /// The recorded error, I/O errors being of kind `Other` and invalid configurations generic
+    fn into_error(self) -> Error {
+        let message = self.message.unwrap_or_default();
+        let kind = match self.kind {
+            RecordedErrorKind::Generic | RecordedErrorKind::InvalidConfig => {
+                ErrorKind::Generic(message)
+            }
+            RecordedErrorKind::Io => ErrorKind::Io(io::Error::new(io::ErrorKind::Other, message)),
+            RecordedErrorKind::PathNotFound => ErrorKind::PathNotFound,
+            RecordedErrorKind::WatchNotFound => ErrorKind::WatchNotFound,
+            RecordedErrorKind::MaxFilesWatch => ErrorKind::MaxFilesWatch,
+        };
+        Error::new(kind).set_paths(self.paths)
+    }
+}
+
+/// Event handler recording what it passes on to another one, see the [module docs](self)
+///
+/// Each event is written out before it is passed on, so a crash loses none of those handled.
+/// Failures to write are passed on as errors, next to the events.
+pub struct RecordingHandler<H> {
+    handler: H,
+    writer: BufWriter<Box<dyn Write + Send>>,
+    start: Instant,
+}
+
+impl<H: EventHandler> RecordingHandler<H> {
+    /// Record to `writer` the events passed on to `handler`
+    pub fn new<W: Write + Send + 'static>(writer: W, handler: H) -> Self {
+        RecordingHandler {
+            handler,
+            writer: BufWriter::new(Box::new(writer)),
+            start: Instant::now(),
+        }
+    }
+
+    /// Record to the file at `path`, replacing it, the events passed on to `handler`
+    pub fn create<P: AsRef<Path>>(path: P, handler: H) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::create(path).map_err(|e| Error::io(e).add_path(path.to_path_buf()))?;
+        Ok(Self::new(file, handler))
+    }
+
+    fn record(&mut self, event: &Result<Event>) -> io::Result<()> {
+        let entry = match event {
+            Ok(event) => Entry::Event(event),
+            Err(error) => Entry::Error(RecordedError::new(error)),
+        };
+        let record = Record {
+            time: self.start.elapsed().as_secs_f64(),
+            entry,
+        };
+        serde_json::to_writer(&mut self.writer, &record)?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()
+    }
+}
+
+impl<H: EventHandler> EventHandler for RecordingHandler<H> {
+    fn handle_event(&mut self, event: Result<Event>) {
+        if let Err(e) = self.record(&event) {
+            self.handler.handle_event(Err(Error::io(e)));
+        }
+        self.handler.handle_event(event);
+    }
+}
+
+impl<H> fmt::Debug for RecordingHandler<H> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RecordingHandler")
+            .field("start", &self.start)
+            .finish()
+    }
+}
+
+/// Watcher playing back a recording of a [`RecordingHandler`], see the [module docs](self)
+///
+/// The recording is read from the [replay file](Config::with_replay_file) when the watcher is
+/// created, and played back once the first path is watched. Events are delivered whatever paths
+/// are watched, as the filesystem isn't looked at, but [`Watcher::watch_with`] filters them by
+/// kind.
+///
+/// The event handler is dropped at the end of the recording, so a channel receiving the events
+/// disconnects. Dropping the watcher stops playback.
+pub struct ReplayWatcher {
+    /// The recording and the handler to play it to, until playback starts
+    pending: Option<(Recording, Box<dyn EventHandler>)>,
+    pace: ReplayPace,
+    filters: WatchFilters,
+    stop: Arc<Stop>,
+}
+
+/// Stops playback when the [`ReplayWatcher`] is dropped
+#[derive(Default)]
+struct Stop {
+    stopped: Mutex<bool>,
+    wakeup: Condvar,
+}
+
+impl Stop {
+    /// Wait until `time` has passed since `start`, returning early if playback was stopped.
+    ///
+    /// Returns whether it was.
+    fn wait(&self, start: Instant, time: Duration) -> bool {
+        let mut stopped = self.stopped.lock().unwrap_or_else(|e| e.into_inner());
+        loop {
+            let elapsed = start.elapsed();
+            if *stopped || elapsed >= time {
+                return *stopped;
+            }
+            stopped = self
+                .wakeup
+                .wait_timeout(stopped, time - elapsed)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+    }
+
+    fn stop(&self) {
+        *self.stopped.lock().unwrap_or_else(|e| e.into_inner()) = true;
+        self.wakeup.notify_all();
+    }
+}
+
+impl ReplayWatcher {
+    /// Start playback, unless it has started already
+    ///
+    /// Fails if the playback thread can't be spawned, which drops the recording.
+    fn play(&mut self) -> Result<()> {
+        let (records, mut handler) = match self.pending.take() {
+            Some(pending) => pending,
+            None => return Ok(()),
+        };
+        let pace = self.pace;
+        let stop = Arc::clone(&self.stop);
+        thread::Builder::new()
+            .name("notify-rs replay".to_string())
+            .spawn(move || {
+                let start = Instant::now();
+                for (time, event) in records {
+                    let time = match pace {
+                        ReplayPace::RealTime => time,
+                        ReplayPace::AsFastAsPossible => Duration::ZERO,
+                    };
+                    if stop.wait(start, time) {
+                        return;
+                    }
+                    handler.handle_event(event);
+                }
+            })
+            .map_err(Error::io)?;
+        Ok(())
+    }
+}
+
+/// Events of a recording, with the time since it started
+type Recording = Vec<(Duration, Result<Event>)>;
+
+/// Read the recording in `file`
+fn read_recording(file: &Path) -> Result<Recording> {
+    let to_error = |e| Error::io(e).add_path(file.to_path_buf());
+    let reader = BufReader::new(File::open(file).map_err(to_error)?);
+    let mut records = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line = line.map_err(to_error)?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let invalid = |reason: &dyn fmt::Display| {
+            Error::generic(&format!("invalid record on line {}: {}", i + 1, reason))
+                .add_path(file.to_path_buf())
+        };
+        let record: Record<Event> = serde_json::from_str(&line).map_err(|e| invalid(&e))?;
+        if !(0.0..=f64::from(u32::MAX)).contains(&record.time) {
+            return Err(invalid(&"time out of range"));
+        }
+        let event = match record.entry {
+            Entry::Event(event) => Ok(event),
+            Entry::Error(error) => Err(error.into_error()),
+        };
+        records.push((Duration::from_secs_f64(record.time), event));
+    }
+    Ok(records)
+}
+
+impl Watcher for ReplayWatcher {
+    /// Create a new watcher, reading the [replay file](Config::with_replay_file).
+    fn new<F: EventHandler>(event_handler: F, config: Config) -> Result<Self> {
+        let file = config
+            .replay_file()
+            .ok_or_else(|| Error::generic("no replay file configured"))?;
+        let records = read_recording(file)?;
+        let filters = WatchFilters::default();
+        let event_handler = filters.wrap_handler(event_handler);
+        Ok(ReplayWatcher {
+            pending: Some((records, event_handler)),
+            pace: config.replay_pace(),
+            filters,
+            stop: Arc::default(),
+        })
+    }
+
+    fn watch(&mut self, path: &Path, recursive_mode: RecursiveMode) -> Result<()> {
+        self.watch_with(path, WatchOptions::new(recursive_mode))
+    }
+
+    fn watch_with(&mut self, path: &Path, options: WatchOptions) -> Result<()> {
+        let filters = self.filters.clone();
+        filters.add(path.to_path_buf(), options, || self.play())
+    }
+
+    fn unwatch(&mut self, path: &Path) -> Result<()> {
+        self.filters.unwatch(path)
+    }
+
+    fn watches(&self) -> Result<Vec<WatchInfo>> {
+        // no kernel watches
+        Ok(self.filters.watch_infos(|_, _| 0))
+    }
+
+    fn kind() -> crate::WatcherKind {
+        crate::WatcherKind::ReplayWatcher
+    }
+}
+
+impl fmt::Debug for ReplayWatcher {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ReplayWatcher")
+            .field("playing", &self.pending.is_none())
+            .field("pace", &self.pace)
+            .field("watches", &self.filters)
+            .finish()
+    }
+}
+
+impl Drop for ReplayWatcher {
+    fn drop(&mut self) {
+        self.stop.stop();
+    }
+}
+
+#[test]
+fn replays_recorded_events() {
+    use crate::event::{CreateKind, EventKind, ModifyKind};
+    use std::sync::mpsc;
+
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("events.jsonl");
+    let events = [
+        Event::new(EventKind::Create(CreateKind::File)).add_path("/srv/a".into()),
+        Event::new(EventKind::Modify(ModifyKind::Any))
+            .add_path("/srv/a".into())
+            .set_tracker(7),
+    ];
+
+    let (tx, rx) = mpsc::channel();
+    let mut recorder = RecordingHandler::create(&file, tx).unwrap();
+    recorder.handle_event(Ok(events[0].clone()));
+    thread::sleep(Duration::from_millis(50));
+    recorder.handle_event(Err(Error::path_not_found().add_path("/srv/b".into())));
+    recorder.handle_event(Ok(events[1].clone()));
+    drop(recorder);
+    // passed on as well
+    assert_eq!(rx.try_iter().count(), 3);
+
+    for pace in [ReplayPace::AsFastAsPossible, ReplayPace::RealTime] {
+        let (tx, rx) = mpsc::channel();
+        let config = Config::default()
+            .with_replay_file(&file)
+            .with_replay_pace(pace);
+        let mut watcher = ReplayWatcher::new(tx, config).unwrap();
+        let start = Instant::now();
+        watcher
+            .watch(Path::new("/srv"), RecursiveMode::Recursive)
+            .unwrap();
+        let replayed: Vec<_> = rx.iter().collect();
+        if pace == ReplayPace::RealTime {
+            assert!(start.elapsed() >= Duration::from_millis(50));
+        }
+
+        assert_eq!(replayed.len(), 3);
+        assert_eq!(replayed[0].as_ref().unwrap(), &events[0]);
+        let error = replayed[1].as_ref().unwrap_err();
+        assert!(matches!(error.kind, ErrorKind::PathNotFound));
+        assert_eq!(error.paths, [PathBuf::from("/srv/b")]);
+        assert_eq!(replayed[2].as_ref().unwrap(), &events[1]);
+    }
+}
@@ -0,0 +1,233 @@
+//! Async `Stream` of watcher events
+//!
+//! Only available with the `async` feature.
+//!
+//! [`EventStream`] implements `futures_core::Stream<Item = Result<Event>>` and can be used with any
+//! async runtime, as it does not rely on blocking inside the runtime nor on crossbeam-channel.
+//!
+//! ```no_run
+//! # use notify::{Config, RecommendedWatcher, RecursiveMode, Watcher};
+//! # use notify::stream::EventStream;
+//! # use futures::StreamExt;
+//! # use std::path::Path;
+//! # async fn run() -> notify::Result<()> {
+//! let (mut watcher, mut stream) = EventStream::new::<RecommendedWatcher>(Config::default())?;
+//! watcher.watch(Path::new("."), RecursiveMode::Recursive)?;
+//!
+//! while let Some(res) = stream.next().await {
+//!     println!("{:?}", res);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::event::Flag;
+use crate::{Config, Event, EventHandler, EventKind, Result, Watcher};
+use futures_core::Stream;
+use std::{
+    collections::VecDeque,
+    fmt,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+};
+
+/// Default amount of events buffered before further events are dropped.
+pub const DEFAULT_CAPACITY: usize = 1024;
+
+struct Shared {
+    queue: VecDeque<Result<Event>>,
+    capacity: usize,
+    waker: Option<Waker>,
+    /// Events were dropped since the buffer was last below capacity
+    overflowed: bool,
+    /// The handler was dropped, no more events will arrive
+    closed: bool,
+    /// The stream was dropped, events are discarded
+    abandoned: bool,
+}
+
+type SharedState = Arc<Mutex<Shared>>;
+
+/// Asynchronous stream of events.
+///
+/// Ends after the [`EventStreamHandler`] feeding it was dropped, which happens when its watcher
+/// is dropped, and all buffered events were consumed.
+///
+/// The watcher backend never waits for the stream. Events arriving while the buffer is full are
+/// dropped, and a single event with [`Flag::Rescan`] follows the buffered ones to tell so.
+pub struct EventStream {
+    shared: SharedState,
+}
+
+/// [`EventHandler`] feeding an [`EventStream`].
+///
+/// Once the stream buffer is full, or if the stream was dropped, events are discarded.
+pub struct EventStreamHandler {
+    shared: SharedState,
+}
+
+impl EventStream {
+    /// Create a new watcher of kind `W` together with a stream of its events.
+    ///
+    /// Buffers up to [`DEFAULT_CAPACITY`] events.
+    pub fn new<W: Watcher>(config: Config) -> Result<(W, EventStream)> {
+        Self::with_capacity(config, DEFAULT_CAPACITY)
+    }
+
+    /// Create a new watcher of kind `W` together with a stream of its events, buffering up to
+    /// `capacity` events.
+    pub fn with_capacity<W: Watcher>(config: Config, capacity: usize) -> Result<(W, EventStream)> {
+        let (handler, stream) = Self::channel(capacity);
+        let watcher = W::new(handler, config)?;
+        Ok((watcher, stream))
+    }
+
+    /// Create a stream together with the [`EventHandler`] feeding it.
+    ///
+    /// A `capacity` of zero is treated as one.
+    pub fn channel(capacity: usize) -> (EventStreamHandler, EventStream) {
+        let shared = Arc::new(Mutex::new(Shared {
+            queue: VecDeque::new(),
+            capacity: capacity.max(1),
+            waker: None,
+            overflowed: false,
+            closed: false,
+            abandoned: false,
+        }));
+        (
+            EventStreamHandler {
+                shared: Arc::clone(&shared),
+            },
+            EventStream { shared },
+        )
+    }
+}
+
+impl Stream for EventStream {
+    type Item = Result<Event>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut shared = self.shared.lock().unwrap_or_else(|e| e.into_inner());
+
+        if let Some(event) = shared.queue.pop_front() {
+            return Poll::Ready(Some(event));
+        }
+        if shared.closed {
+            return Poll::Ready(None);
+        }
+
+        shared.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let shared = self.shared.lock().unwrap_or_else(|e| e.into_inner());
+        (shared.queue.len(), None)
+    }
+}
+
+impl Drop for EventStream {
+    fn drop(&mut self) {
+        let mut shared = self.shared.lock().unwrap_or_else(|e| e.into_inner());
+        shared.abandoned = true;
+        shared.queue.clear();
+    }
+}
+
+impl EventHandler for EventStreamHandler {
+    fn handle_event(&mut self, event: Result<Event>) {
+        let mut shared = self.shared.lock().unwrap_or_else(|e| e.into_inner());
+        if shared.abandoned {
+            return;
+        }
+
+        if shared.queue.len() < shared.capacity {
+            shared.overflowed = false;
+            shared.queue.push_back(event);
+        } else if !shared.overflowed {
+            // the one event over capacity, until the stream catches up
+            shared.overflowed = true;
+            let rescan = Event::new(EventKind::Other).set_flag(Flag::Rescan);
+            shared.queue.push_back(Ok(rescan));
+        } else {
+            return;
+        }
+        if let Some(waker) = shared.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl Drop for EventStreamHandler {
+    fn drop(&mut self) {
+        let mut shared = self.shared.lock().unwrap_or_else(|e| e.into_inner());
+        shared.closed = true;
+        if let Some(waker) = shared.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl fmt::Debug for EventStream {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("EventStream").finish_non_exhaustive()
+    }
+}
+
+impl fmt::Debug for EventStreamHandler {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("EventStreamHandler").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EventKind;
+    use futures::{executor::block_on, StreamExt};
+    use std::thread;
+
+    #[test]
+    fn stream_ends_after_handler_is_dropped() {
+        let (mut handler, stream) = EventStream::channel(3);
+
+        let producer = thread::spawn(move || {
+            for _ in 0..3 {
+                handler.handle_event(Ok(Event::new(EventKind::Any)));
+            }
+        });
+
+        producer.join().unwrap();
+        let events: Vec<_> = block_on(stream.collect());
+        assert_eq!(events.len(), 3);
+    }
+
+    #[test]
+    fn full_stream_drops_events_for_a_rescan() {
+        let (mut handler, mut stream) = EventStream::channel(2);
+        for _ in 0..5 {
+            handler.handle_event(Ok(Event::new(EventKind::Any)));
+        }
+        let flags: Vec<_> = (0..3)
+            .map(|_| block_on(stream.next()).unwrap().unwrap().flag())
+            .collect();
+        assert_eq!(flags, [None, None, Some(Flag::Rescan)]);
+
+        // events pass again once the stream caught up
+        handler.handle_event(Ok(Event::new(EventKind::Any)));
+        drop(handler);
+        let events: Vec<_> = block_on(stream.collect());
+        assert_eq!(events.len(), 1);
+        assert!(!events[0].as_ref().unwrap().need_rescan());
+    }
+
+    #[test]
+    fn dropped_stream_does_not_block_handler() {
+        let (mut handler, stream) = EventStream::channel(1);
+        drop(stream);
+
+        handler.handle_event(Ok(Event::new(EventKind::Any)));
+        handler.handle_event(Ok(Event::new(EventKind::Any)));
+    }
+}
@@ -0,0 +1,369 @@
+//! In-memory copy of a watched tree, kept in sync by its events
+//!
+//! A [`FileTree`] lists a directory tree once and then follows its [`Event`]s. It doesn't trust
+//! what an event claims happened: it looks at the paths the event names and updates its entries
+//! to what it finds there, so renames, missed events and coalesced events end up in the same
+//! state as a fresh listing. Events asking for a [rescan](Event::need_rescan) relist the subtree
+//! they name, or the whole tree.
+//!
+//! ```no_run
+//! use notify::tree::FileTree;
+//! use notify::{recommended_watcher, RecursiveMode, Result, Watcher};
+//! use std::path::Path;
+//! use std::sync::mpsc;
+//!
+//! fn main() -> Result<()> {
+//!     let (tx, rx) = mpsc::channel();
+//!     let mut watcher = recommended_watcher(tx)?;
+//!     // watch before listing, so no change falls in between
+//!     watcher.watch(Path::new("/srv"), RecursiveMode::Recursive)?;
+//!     let mut tree = FileTree::new("/srv")?;
+//!
+//!     for event in rx {
+//!         for change in tree.apply(&event?) {
+//!             println!("{:?}", change);
+//!         }
+//!         println!("{} entries below /srv", tree.list("/srv").map_or(0, |e| e.len()));
+//!     }
+//!     Ok(())
+//! }
+//! ```
+
+use crate::{Error, Event, Result};
+use std::collections::BTreeMap;
+use std::fs::{self, Metadata};
+use std::io;
+use std::ops::Bound;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use walkdir::WalkDir;
+
+/// Type of an entry of a [`FileTree`]
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub enum EntryKind {
+    /// Regular file
+    File,
+
+    /// Directory
+    Dir,
+
+    /// Symbolic link, which isn't followed
+    Symlink,
+
+    /// Anything else, like sockets or devices
+    Other,
+}
+
+/// Metadata of an entry of a [`FileTree`], as of its last update
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+pub struct EntryInfo {
+    /// Type of the entry
+    pub kind: EntryKind,
+
+    /// Size in bytes
+    pub len: u64,
+
+    /// Last modification time, where the platform has one
+    pub modified: Option<SystemTime>,
+
+    /// Whether the entry is read-only
+    pub readonly: bool,
+}
+
+impl EntryInfo {
+    fn new(metadata: &Metadata) -> Self {
+        let file_type = metadata.file_type();
+        let kind = if file_type.is_symlink() {
+            EntryKind::Symlink
+        } else if file_type.is_dir() {
+            EntryKind::Dir
+        } else if file_type.is_file() {
+            EntryKind::File
+        } else {
+            EntryKind::Other
+        };
+        EntryInfo {
+            kind,
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+            readonly: metadata.permissions().readonly(),
+        }
+    }
+}
+
+/// Change of a [`FileTree`], found by comparing it with the filesystem
+///
+/// A rename shows up as the removal of the old path and the addition of the new one, with all
+/// entries below them for directories.
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+pub enum TreeChange {
+    /// The path was added to the tree
+    Added(PathBuf),
+
+    /// The metadata of the path changed
+    Modified(PathBuf),
+
+    /// The path was removed from the tree
+    Removed(PathBuf),
+}
+
+/// Listing of a directory tree kept in sync by events, see the [module docs](self)
+///
+/// The root and everything below it is listed, without following symbolic links. Paths of
+/// events are expected below the root as given, events of other paths are ignored.
+#[derive(Clone, Debug)]
+pub struct FileTree {
+    root: PathBuf,
+    entries: BTreeMap<PathBuf, EntryInfo>,
+}
+
+impl FileTree {
+    /// List the tree at `root`
+    pub fn new<P: Into<PathBuf>>(root: P) -> Result<Self> {
+        let root = root.into();
+        fs::symlink_metadata(&root).map_err(|e| Error::io(e).add_path(root.clone()))?;
+        let mut tree = FileTree {
+            entries: BTreeMap::new(),
+            root,
+        };
+        tree.entries = scan(&tree.root);
+        Ok(tree)
+    }
+
+    /// Returns the root of the tree
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Returns the metadata of `path`, if it is in the tree
+    pub fn get<P: AsRef<Path>>(&self, path: P) -> Option<&EntryInfo> {
+        self.entries.get(path.as_ref())
+    }
+
+    /// Returns the entries right below the directory `dir`, sorted by path
+    ///
+    /// Returns `None` if `dir` isn't a directory of the tree.
+    pub fn list<P: AsRef<Path>>(&self, dir: P) -> Option<Vec<(&Path, &EntryInfo)>> {
+        let dir = dir.as_ref();
+        if self.get(dir)?.kind != EntryKind::Dir {
+            return None;
+        }
+        Some(
+            self.below(dir)
+                .filter(|(path, _)| path.parent() == Some(dir))
+                .map(|(path, info)| (path.as_path(), info))
+                .collect(),
+        )
+    }
+
+    /// Returns all entries of the tree, the root included, sorted by path
+    pub fn iter(&self) -> impl Iterator<Item = (&Path, &EntryInfo)> {
+        self.entries
+            .iter()
+            .map(|(path, info)| (path.as_path(), info))
+    }
+
+    /// Returns the number of entries, the root included
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether the tree has no entries, once its root is gone
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Update the tree for `event`, returning how it changed
+    ///
+    /// Each path of the event is looked at again. Directories that are new to the tree are
+    /// listed with everything below them, and removed paths take their entries with them.
+    /// Events asking for a rescan relist their paths, or the whole tree if they name none.
+    pub fn apply(&mut self, event: &Event) -> Vec<TreeChange> {
+        let mut changes = Vec::new();
+        if event.need_rescan() {
+            if event.paths.is_empty() {
+                let root = self.root.clone();
+                self.sync(&root, scan(&root), &mut changes);
+            }
+            for path in &event.paths {
+                self.rescan_into(path, &mut changes);
+            }
+        } else {
+            for path in &event.paths {
+                self.refresh(path, &mut changes);
+            }
+        }
+        changes
+    }
+
+    /// List `path` and everything below it again, returning how the tree changed
+    pub fn rescan<P: AsRef<Path>>(&mut self, path: P) -> Vec<TreeChange> {
+        let mut changes = Vec::new();
+        self.rescan_into(path.as_ref(), &mut changes);
+        changes
+    }
+
+    fn rescan_into(&mut self, path: &Path, changes: &mut Vec<TreeChange>) {
+        if let Some(top) = self.unknown_ancestor(path) {
+            self.sync(&top, scan(&top), changes);
+        }
+    }
+
+    /// Update the entry of `path` to what is found there
+    fn refresh(&mut self, path: &Path, changes: &mut Vec<TreeChange>) {
+        let top = match self.unknown_ancestor(path) {
+            Some(top) => top,
+            None => return,
+        };
+        if top != path {
+            // its parent is missing too, so list from the first directory missing
+            return self.sync(&top, scan(&top), changes);
+        }
+        let info = match fs::symlink_metadata(path) {
+            Ok(metadata) => EntryInfo::new(&metadata),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return self.sync(path, BTreeMap::new(), changes)
+            }
+            // leave the entry alone, as nothing is known about it
+            Err(_) => return,
+        };
+        let known_dir = self.get(path).map(|old| old.kind) == Some(EntryKind::Dir);
+        if info.kind == EntryKind::Dir && !known_dir {
+            return self.sync(path, scan(path), changes);
+        }
+        if info.kind != EntryKind::Dir && known_dir {
+            // a directory replaced by a file, drop what was below it
+            let below: Vec<_> = self.below(path).map(|(path, _)| path.clone()).collect();
+            for path in below.into_iter().rev() {
+                self.entries.remove(&path);
+                changes.push(TreeChange::Removed(path));
+            }
+        }
+        self.update(path.to_path_buf(), info, changes);
+    }
+
+    /// Returns the outermost path from `path` up that is missing from the tree, or `path` itself
+    /// if it isn't, or `None` if `path` is outside of the tree
+    fn unknown_ancestor(&self, path: &Path) -> Option<PathBuf> {
+        if !path.starts_with(&self.root) {
+            return None;
+        }
+        let mut top = path;
+        while top != self.root {
+            match top.parent() {
+                Some(parent) if !self.entries.contains_key(parent) => top = parent,
+                _ => break,
+            }
+        }
+        Some(top.to_path_buf())
+    }
+
+    /// Replace the entries of `path` and below with `found`, a listing of the same paths
+    fn sync(
+        &mut self,
+        path: &Path,
+        found: BTreeMap<PathBuf, EntryInfo>,
+        changes: &mut Vec<TreeChange>,
+    ) {
+        let gone: Vec<_> = self
+            .entries
+            .range::<Path, _>((Bound::Included(path), Bound::Unbounded))
+            .map(|(path, _)| path)
+            .take_while(|entry| entry.starts_with(path))
+            .filter(|entry| !found.contains_key(entry.as_path()))
+            .cloned()
+            .collect();
+        // children before their directories
+        for path in gone.into_iter().rev() {
+            self.entries.remove(&path);
+            changes.push(TreeChange::Removed(path));
+        }
+        for (path, info) in found {
+            self.update(path, info, changes);
+        }
+    }
+
+    fn update(&mut self, path: PathBuf, info: EntryInfo, changes: &mut Vec<TreeChange>) {
+        match self.entries.get(&path) {
+            Some(old) if *old == info => {}
+            Some(_) => {
+                self.entries.insert(path.clone(), info);
+                changes.push(TreeChange::Modified(path));
+            }
+            None => {
+                self.entries.insert(path.clone(), info);
+                changes.push(TreeChange::Added(path));
+            }
+        }
+    }
+
+    /// Returns the entries below `dir`, without `dir` itself
+    fn below<'a>(&'a self, dir: &Path) -> impl Iterator<Item = (&'a PathBuf, &'a EntryInfo)> {
+        let prefix = dir.to_path_buf();
+        self.entries
+            .range::<Path, _>((Bound::Excluded(dir), Bound::Unbounded))
+            .take_while(move |(path, _)| path.starts_with(&prefix))
+    }
+}
+
+/// Returns the entries of `path` and everything below it
+fn scan(path: &Path) -> BTreeMap<PathBuf, EntryInfo> {
+    WalkDir::new(path)
+        .follow_links(false)
+        .into_iter()
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let info = EntryInfo::new(&entry.metadata().ok()?);
+            Some((entry.into_path(), info))
+        })
+        .collect()
+}
+
+#[test]
+fn file_tree_follows_renames_and_rescans() {
+    use crate::event::{CreateKind, EventKind, Flag, ModifyKind, RenameMode};
+
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    fs::create_dir_all(root.join("a/b")).unwrap();
+    fs::write(root.join("a/b/file"), b"data").unwrap();
+    let mut tree = FileTree::new(root).unwrap();
+    assert_eq!(tree.len(), 4);
+    assert_eq!(tree.get(root.join("a/b/file")).unwrap().len, 4);
+
+    // a directory moved with its contents
+    fs::rename(root.join("a"), root.join("c")).unwrap();
+    let rename = Event::new(EventKind::Modify(ModifyKind::Name(RenameMode::Both)))
+        .add_path(root.join("a"))
+        .add_path(root.join("c"));
+    let changes = tree.apply(&rename);
+    assert_eq!(changes.len(), 6);
+    assert!(changes.contains(&TreeChange::Removed(root.join("a/b/file"))));
+    assert!(changes.contains(&TreeChange::Added(root.join("c/b/file"))));
+    let listing: Vec<_> = tree
+        .list(root)
+        .unwrap()
+        .into_iter()
+        .map(|(p, _)| p)
+        .collect();
+    assert_eq!(listing, [root.join("c")]);
+
+    // a create event claiming a path that is gone again changes nothing
+    let create = Event::new(EventKind::Create(CreateKind::File)).add_path(root.join("gone"));
+    assert!(tree.apply(&create).is_empty());
+
+    // changes missed in an overflow turn up with the rescan
+    fs::remove_file(root.join("c/b/file")).unwrap();
+    fs::write(root.join("c/new"), b"").unwrap();
+    let rescan = Event::new(EventKind::Other).set_flag(Flag::Rescan);
+    let mut changes = tree.apply(&rescan);
+    changes.retain(|change| !matches!(change, TreeChange::Modified(_)));
+    assert_eq!(
+        changes,
+        [
+            TreeChange::Removed(root.join("c/b/file")),
+            TreeChange::Added(root.join("c/new")),
+        ]
+    );
+    assert!(tree.list(root.join("c/new")).is_none());
+}
@@ -5,9 +5,10 @@
 //!
 //! [ref]: https://msdn.microsoft.com/en-us/library/windows/desktop/aa363950(v=vs.85).aspx
 
+use crate::filter::{filtered_handler, WatchFilters};
 use crate::{bounded, unbounded, BoundSender, Config, Receiver, Sender};
 use crate::{event::*, WatcherKind};
-use crate::{Error, EventHandler, RecursiveMode, Result, Watcher};
+use crate::{Error, EventHandler, RecursiveMode, Result, WatchInfo, WatchOptions, Watcher};
 use std::collections::HashMap;
 use std::env;
 use std::ffi::OsString;
@@ -411,12 +412,29 @@ pub struct ReadDirectoryChangesWatcher {
     tx: Sender<Action>,
     cmd_rx: Receiver<Result<PathBuf>>,
     wakeup_sem: HANDLE,
+    filters: WatchFilters,
 }
 
 impl ReadDirectoryChangesWatcher {
     pub fn create(
         event_handler: Arc<Mutex<dyn EventHandler>>,
         meta_tx: Sender<MetaEvent>,
+    ) -> Result<ReadDirectoryChangesWatcher> {
+        let filters = WatchFilters::default();
+        let event_handler = filters.wrap_handler(move |event: Result<Event>| {
+            event_handler
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .handle_event(event)
+        });
+        Self::from_event_handler(Arc::new(Mutex::new(event_handler)), meta_tx, filters)
+    }
+
+    /// Start the server with an `event_handler` already wrapped by `filters`
+    fn from_event_handler(
+        event_handler: Arc<Mutex<dyn EventHandler>>,
+        meta_tx: Sender<MetaEvent>,
+        filters: WatchFilters,
     ) -> Result<ReadDirectoryChangesWatcher> {
         let (cmd_tx, cmd_rx) = unbounded();
 
@@ -432,6 +450,7 @@ impl ReadDirectoryChangesWatcher {
             tx: action_tx,
             cmd_rx,
             wakeup_sem,
+            filters,
         })
     }
 
@@ -469,7 +488,7 @@ impl ReadDirectoryChangesWatcher {
         }
     }
 
-    fn watch_inner(&mut self, path: &Path, recursive_mode: RecursiveMode) -> Result<()> {
+    fn watch_inner(&mut self, path: &Path, options: WatchOptions) -> Result<()> {
         let pb = if path.is_absolute() {
             path.to_owned()
         } else {
@@ -482,7 +501,10 @@ impl ReadDirectoryChangesWatcher {
                 "Input watch path is neither a file nor a directory.",
             ));
         }
-        self.send_action_require_ack(Action::Watch(pb.clone(), recursive_mode), &pb)
+        let filters = self.filters.clone();
+        filters.add(pb.clone(), options, || {
+            self.send_action_require_ack(Action::Watch(pb.clone(), options.recursive_mode()), &pb)
+        })
     }
 
     fn unwatch_inner(&mut self, path: &Path) -> Result<()> {
@@ -492,6 +514,7 @@ impl ReadDirectoryChangesWatcher {
             let p = env::current_dir().map_err(Error::io)?;
             p.join(path)
         };
+        self.filters.remove(&pb);
         let res = self
             .tx
             .send(Action::Unwatch(pb))
@@ -506,12 +529,16 @@ impl Watcher for ReadDirectoryChangesWatcher {
         // create dummy channel for meta event
         // TODO: determine the original purpose of this - can we remove it?
         let (meta_tx, _) = unbounded();
-        let event_handler = Arc::new(Mutex::new(event_handler));
-        Self::create(event_handler, meta_tx)
+        let (event_handler, filters, _) = filtered_handler(event_handler, &config);
+        Self::from_event_handler(Arc::new(Mutex::new(event_handler)), meta_tx, filters)
     }
 
     fn watch(&mut self, path: &Path, recursive_mode: RecursiveMode) -> Result<()> {
-        self.watch_inner(path, recursive_mode)
+        self.watch_inner(path, WatchOptions::new(recursive_mode))
+    }
+
+    fn watch_with(&mut self, path: &Path, options: WatchOptions) -> Result<()> {
+        self.watch_inner(path, options)
     }
 
     fn unwatch(&mut self, path: &Path) -> Result<()> {
@@ -524,6 +551,11 @@ impl Watcher for ReadDirectoryChangesWatcher {
         rx.recv()?
     }
 
+    fn watches(&self) -> Result<Vec<WatchInfo>> {
+        // every watched path has a directory handle of its own
+        Ok(self.filters.watch_infos(|_, _| 1))
+    }
+
     fn kind() -> crate::WatcherKind {
         WatcherKind::ReadDirectoryChangesWatcher
     }